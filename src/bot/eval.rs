@@ -1,7 +1,7 @@
 use std::fmt::Debug;
-use std::sync::Arc;
 use crate::core::{
     board::{Board, Move, Stone, Player},
+    pattern::Direction,
     rule::Rule,
 };
 
@@ -36,29 +36,31 @@ fn pattern_count(board: &Board) -> PatternCount {
         five_white: 0,
     };
     
+    let size = board.size();
+
     // horizontal count
-    for x in 0..15 {
+    for x in 0..size {
         scan_line(board, x, 0, 0, 1, &mut result);
     }
 
     // vertical count
-    for y in 0..15 {
+    for y in 0..size {
         scan_line(board, 0, y, 1, 0, &mut result);
     }
 
     // diagonal down (top left --> bottom right)
-    for x in 0..15 {
+    for x in 0..size {
         scan_line(board, x, 0, 1, 1, &mut result);
     }
-    for y in 1..15 {
+    for y in 1..size {
         scan_line(board, 0, y, 1, 1, &mut result);
     }
 
     // diagonal up (bottom left --> top right)
-    for x in 0..15 {
-        scan_line(board, x, 14, 1, -1, &mut result);
+    for x in 0..size {
+        scan_line(board, x, size - 1, 1, -1, &mut result);
     }
-    for y in 0..14 {
+    for y in 0..size - 1 {
         scan_line(board, 0, y, 1, -1, &mut result);
     }
 
@@ -73,15 +75,28 @@ fn scan_line(
     dy: isize,
     result: &mut PatternCount,
 ) {
-    let mut x = start_x as isize;
-    let mut y = start_y as isize;
+    let direction = match (dx, dy) {
+        (1, 0) => Direction::Horizontal,
+        (0, 1) => Direction::Vertical,
+        (1, 1) => Direction::DiagDown,
+        _ => Direction::DiagUp,
+    };
+    // cells of the line are read as bits from the start
+    let line = board.line_bits(Move { x: start_x, y: start_y }, direction);
+    let mut bit = line.pos;
 
     let mut open = false;
     let mut cnt: usize = 0;
     let mut last = Stone::None;
     
-    while 0 <= x && x < 15 && 0 <= y && y < 15 {
-        let stone = board.get(Move { x: x as usize, y: y as usize });
+    while line.mask & (1 << bit) != 0 {
+        let stone = if line.black & (1 << bit) != 0 {
+            Stone::Black
+        } else if line.white & (1 << bit) != 0 {
+            Stone::White
+        } else {
+            Stone::None
+        };
 
         if stone == last {
             cnt += 1;
@@ -121,8 +136,7 @@ fn scan_line(
             last = stone;
         }
 
-        x += dx;
-        y += dy;
+        bit += 1;
     }
 
     if cnt >= 5 {
@@ -0,0 +1,368 @@
+use std::time::Instant;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use crate::core::board::{Board, Move};
+use crate::core::opening::Choice;
+use crate::core::rule::{PutOutcome, Rule};
+use super::eval::Eval;
+use super::model::{Model, SearchInfo, SearchLimits, SearchResult, SearchStats, DEFAULT_SEED};
+use super::prune::Prune;
+use super::vcf::{empty_cells, five_points, five_shapes};
+
+// Monte Carlo tree search with UCT selection.
+// Each playout descends the tree by the upper confidence bound, adds a node,
+// then plays a short rollout by the policy and backs up the result.
+// The subtree of the position after the opponent move is kept for the next move.
+
+/// playouts at the root by `MctsModel::new`
+pub const DEFAULT_PLAYOUTS: u32 = 20_000;
+/// exploration constant of uct by `MctsModel::new`
+pub const DEFAULT_EXPLORATION: f32 = 1.4;
+/// plies of a rollout by `MctsModel::new`, then the eval decides the result
+pub const DEFAULT_ROLLOUT_DEPTH: u32 = 12;
+
+// candidates compared by the eval at each ply of a rollout
+const ROLLOUT_SAMPLES: usize = 4;
+// eval difference which makes a rollout move e times as likely
+const TEMPERATURE: f32 = 50.0;
+// eval at the end of a rollout which counts as a win rate of 73%
+const EVAL_SCALE: f32 = 200.0;
+// win rate within this from a half is regarded as balanced when choosing color
+const BALANCED: f32 = 0.05;
+
+/// scores of the results and the reports are the win rate in percent
+#[derive(Debug)]
+pub struct MctsModel<E: Eval, P: Prune, R: Rule> {
+    pub eval: E,
+    pub prune: P,
+    pub rule: R,
+    /// playouts of the root at most, including the ones kept from the previous move
+    /// (the limits can stop the search earlier)
+    pub playouts: u32,
+    pub exploration: f32,
+    pub rollout_depth: u32,
+    // picks the untried moves and the rollout moves
+    seed: u64,
+    rng: StdRng,
+    // tree of the previous search (root first), empty at the start
+    tree: Vec<Node>,
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    /// move to this position (the last move at the root)
+    mv: Move,
+    hash: u64,
+    children: Vec<usize>,
+    // candidates not added as children yet
+    untried: Vec<Move>,
+    visits: u32,
+    // sum of the results for the player who made the move
+    reward: f32,
+    // the game ended by the move
+    end: Option<PutOutcome>,
+}
+
+impl Node {
+    fn new(mv: Move, hash: u64, untried: Vec<Move>, end: Option<PutOutcome>) -> Self {
+        Self {
+            mv,
+            hash,
+            children: Vec::new(),
+            untried,
+            visits: 0,
+            reward: 0.0,
+            end,
+        }
+    }
+
+    /// average result for the player who made the move
+    fn rate(&self) -> f32 {
+        if self.visits == 0 {
+            return 0.5;
+        }
+        self.reward / self.visits as f32
+    }
+}
+
+impl<E: Eval, P: Prune, R: Rule> MctsModel<E, P, R> {
+    pub fn new(eval: E, prune: P, rule: R) -> Self {
+        Self::with_seed(eval, prune, rule, DEFAULT_SEED)
+    }
+
+    /// with a fixed number of playouts the tree is the same for the same seed
+    pub fn with_seed(eval: E, prune: P, rule: R, seed: u64) -> Self {
+        tracing::info!("mcts model seed: {}", seed);
+        Self {
+            eval,
+            prune,
+            rule,
+            playouts: DEFAULT_PLAYOUTS,
+            exploration: DEFAULT_EXPLORATION,
+            rollout_depth: DEFAULT_ROLLOUT_DEPTH,
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+            tree: Vec::new(),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// most visited move and its win rate for the side to move,
+    /// playing out until the playouts or the limits
+    pub fn search(&mut self, board: &Board, mv: Move, limits: SearchLimits) -> SearchResult {
+        let start = Instant::now();
+        let deadline = limits.budget().map(|b| start + b);
+        self.reuse(board, mv);
+
+        let mut stats = SearchStats::default();
+        while self.tree[0].visits < self.playouts {
+            // the first playout always runs to have a move
+            if stats.nodes > 0
+                && (limits.stop.is_stopped() || deadline.is_some_and(|d| Instant::now() >= d))
+            {
+                break;
+            }
+            let depth = self.playout(board);
+            stats.nodes += 1;
+            stats.max_depth = stats.max_depth.max(depth);
+        }
+
+        // most visited chain from the root
+        let mut pv = Vec::new();
+        let mut node = 0;
+        while let Some(&child) = self.tree[node].children.iter().max_by_key(|&&c| self.tree[c].visits) {
+            pv.push(self.tree[child].mv);
+            node = child;
+        }
+        let best = pv.first().copied();
+        let score = match self.tree[0].children.iter().max_by_key(|&&c| self.tree[c].visits) {
+            Some(&child) => self.tree[child].rate() * 100.0,
+            None => 50.0,
+        };
+
+        let elapsed = start.elapsed();
+        let info = SearchInfo {
+            depth: stats.max_depth,
+            score,
+            pv,
+            nps: (stats.nodes as f64 / elapsed.as_secs_f64().max(1e-6)) as u64,
+            elapsed,
+            stats,
+        };
+        tracing::debug!("{:?}, root visits: {}", info, self.tree[0].visits);
+        if let Some(tx) = &limits.info {
+            let _ = tx.send(info);
+        }
+
+        SearchResult {
+            best,
+            score,
+            depth: stats.max_depth,
+            stats,
+            elapsed,
+        }
+    }
+
+    /// keeps the subtree of the position if the previous tree reached it
+    /// (the position after the own move and the opponent move), or starts a new tree
+    fn reuse(&mut self, board: &Board, mv: Move) {
+        let found = self.tree.first().and_then(|root| {
+            std::iter::once(0)
+                .chain(root.children.iter().copied())
+                .chain(root.children.iter().flat_map(|&c| self.tree[c].children.iter().copied()))
+                .find(|&i| self.tree[i].hash == board.hash() && self.tree[i].mv == mv)
+        });
+
+        self.tree = match found {
+            Some(i) => {
+                let mut tree = Vec::new();
+                self.copy(i, &mut tree);
+                tree
+            },
+            None => {
+                let mut candidates = self.candidates(board, mv);
+                // the root needs a move to expand, if the rule forbids every neighbour
+                // (e.g., pro opening rule) any empty cell can be tried
+                let turn = board.turn();
+                if candidates.iter().all(|&m| !self.rule.is_valid(board, m, turn)) {
+                    candidates = empty_cells(board).collect();
+                }
+                vec![Node::new(mv, board.hash(), candidates, None)]
+            },
+        };
+        tracing::debug!("kept playouts: {}", self.tree[0].visits);
+    }
+
+    /// copies the subtree of the node to the end of the tree, returns its index
+    fn copy(&self, node: usize, tree: &mut Vec<Node>) -> usize {
+        let index = tree.len();
+        tree.push(Node { children: Vec::new(), ..self.tree[node].clone() });
+        for &child in &self.tree[node].children {
+            let child = self.copy(child, tree);
+            tree[index].children.push(child);
+        }
+        index
+    }
+
+    /// selection, expansion, rollout and backup, returns the depth of the added node
+    fn playout(&mut self, board: &Board) -> u32 {
+        let mut board = *board;
+        let mut path = vec![0];
+
+        loop {
+            let node = *path.last().unwrap();
+            if self.tree[node].end.is_some() {
+                break;
+            }
+
+            // expand a random candidate
+            if !self.tree[node].untried.is_empty() {
+                let untried = &mut self.tree[node].untried;
+                let mv = untried.swap_remove(self.rng.gen_range(0..untried.len()));
+                let turn = board.turn();
+                let outcome = match self.rule.put(&mut board, mv, turn) {
+                    Ok(outcome) => outcome,
+                    // forbidden, never tried again
+                    Err(_) => continue,
+                };
+                let (untried, end) = match outcome {
+                    PutOutcome::Continue => (self.candidates(&board, mv), None),
+                    end => (Vec::new(), Some(end)),
+                };
+                self.tree.push(Node::new(mv, board.hash(), untried, end));
+                let child = self.tree.len() - 1;
+                self.tree[node].children.push(child);
+                path.push(child);
+                break;
+            }
+
+            let Some(child) = self.select(node) else {
+                // no legal move
+                break;
+            };
+            let turn = board.turn();
+            board.put(self.tree[child].mv, turn.to_stone());
+            path.push(child);
+        }
+
+        // result for the player who made the last move
+        let leaf = &self.tree[*path.last().unwrap()];
+        let mut result = match leaf.end {
+            Some(PutOutcome::Win) => 1.0,
+            Some(_) => 0.5,
+            None => 1.0 - self.rollout(&mut board, leaf.mv),
+        };
+        for &node in path.iter().rev() {
+            self.tree[node].visits += 1;
+            self.tree[node].reward += result;
+            result = 1.0 - result;
+        }
+        path.len() as u32 - 1
+    }
+
+    /// moves of a new node, only the fives if the side to move has them,
+    /// or only the blocks if the opponent has fives
+    /// (random expansion rarely finds them among the other candidates)
+    fn candidates(&self, board: &Board, last: Move) -> Vec<Move> {
+        let candidates = self.prune.possible(board, last);
+        let turn = board.turn();
+        for player in [turn, turn.next()] {
+            let fives = five_points(&self.rule, board, five_shapes(board, candidates.iter().copied(), player), player);
+            if !fives.is_empty() {
+                return fives;
+            }
+        }
+        candidates
+    }
+
+    /// child of the highest upper confidence bound
+    fn select(&self, node: usize) -> Option<usize> {
+        let ln = (self.tree[node].visits.max(1) as f32).ln();
+        let ucb = |c: usize| {
+            let child = &self.tree[c];
+            child.rate() + self.exploration * (ln / child.visits.max(1) as f32).sqrt()
+        };
+        self.tree[node].children
+            .iter()
+            .copied()
+            .max_by(|&a, &b| ucb(a).total_cmp(&ucb(b)))
+    }
+
+    /// plays by the policy, returns the result for the side to move
+    ///
+    /// a few random candidates are compared by the eval,
+    /// and one of them is picked with the softmax of the values
+    fn rollout(&mut self, board: &mut Board, mut last: Move) -> f32 {
+        let player = board.turn();
+        // result for the side to move at the end
+        let result = |turn| if turn == player { 1.0 } else { 0.0 };
+
+        for _ in 0..self.rollout_depth {
+            let turn = board.turn();
+            let mut candidates = self.prune.possible(board, last);
+            candidates.shuffle(&mut self.rng);
+
+            let mut values = Vec::with_capacity(ROLLOUT_SAMPLES);
+            for &mv in candidates.iter().take(ROLLOUT_SAMPLES) {
+                match self.rule.put(board, mv, turn) {
+                    Ok(PutOutcome::Continue) => {
+                        // eval is for the opponent, who moves next
+                        values.push((mv, -self.eval.eval(board, mv)));
+                        board.undo_unchecked(mv);
+                    },
+                    Ok(PutOutcome::Win) => return result(turn),
+                    Ok(PutOutcome::Draw) => return 0.5,
+                    Err(_) => (),
+                }
+            }
+            let Some(max) = values.iter().map(|&(_, v)| v).max_by(f32::total_cmp) else {
+                break;
+            };
+
+            let weights: Vec<f32> = values.iter()
+                .map(|&(_, v)| ((v - max) / TEMPERATURE).exp())
+                .collect();
+            let mut pick = self.rng.gen_range(0.0..weights.iter().sum::<f32>());
+            let mut chosen = values[0].0;
+            for (&(mv, _), w) in values.iter().zip(weights) {
+                chosen = mv;
+                if pick < w {
+                    break;
+                }
+                pick -= w;
+            }
+            board.put(chosen, turn.to_stone());
+            last = chosen;
+        }
+
+        // win rate by the eval for the side to move
+        let rate = 1.0 / (1.0 + (-self.eval.eval(board, last) / EVAL_SCALE).exp());
+        if board.turn() == player { rate } else { 1.0 - rate }
+    }
+}
+
+impl<E: Eval, P: Prune, R: Rule> Model for MctsModel<E, P, R> {
+    fn next_move(&mut self, board: &Board, mv: Move, limits: SearchLimits) -> Option<Move> {
+        self.search(board, mv, limits).best
+    }
+
+    fn choose(
+        &mut self, board: &Board, mv: Move, options: &[Choice], limits: SearchLimits
+    ) -> Choice {
+        // win rate of the side to move (white) after the choice
+        let rate = self.search(board, mv, limits).score / 100.0;
+
+        if (rate - 0.5).abs() < BALANCED && options.contains(&Choice::PutTwo) {
+            // let the opponent choose from a balanced position
+            return Choice::PutTwo;
+        }
+
+        let choice = if rate >= 0.5 { Choice::White } else { Choice::Black };
+        tracing::debug!("win rate: {}, choice: {:?}", rate, choice);
+        choice
+    }
+}
\ No newline at end of file
@@ -1,5 +1,7 @@
 pub mod eval;
-pub mod hash;
+pub mod mcts;
 pub mod model;
 pub mod prune;
-pub mod tt;
\ No newline at end of file
+pub mod tt;
+pub mod vcf;
+pub mod vct;
\ No newline at end of file
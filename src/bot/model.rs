@@ -1,48 +1,304 @@
-use std::sync::Mutex;
-use rand::Rng;
-use crate::core::board::{Board, Move, Stone};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::{Duration, Instant};
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use crate::core::board::{Board, Move, Player};
 use crate::core::rule::{PutOutcome, Rule};
+use crate::core::opening::Choice;
 use super::eval::Eval;
-use super::prune::Prune;
-use super::hash::Zobrist;
-use super::tt::{TT, TTEntry};
+use super::prune::{self, Prune};
+use super::tt::{self, TT, TTEntry, Bound};
+use super::vcf::{self, Vcf};
+use super::vct::Vct;
 
 pub trait Model: Send + Sync {
     /// if None, the bot resigns (?)
-    fn next_move(&mut self, board: &Board, mv: Move) -> Option<Move>;
+    fn next_move(&mut self, board: &Board, mv: Move, limits: SearchLimits) -> Option<Move>;
+
+    /// decision of swap openings (color or putting two more stones)
+    fn choose(
+        &mut self, board: &Board, mv: Move, options: &[Choice], limits: SearchLimits
+    ) -> Choice;
+}
+
+/// how long a single search may run
+#[derive(Debug, Clone)]
+pub struct SearchLimits {
+    /// maximum depth of iterative deepening
+    pub depth: u32,
+    /// time for this move
+    pub move_time: Option<Duration>,
+    /// game clock of the side to move
+    pub clock: Option<Clock>,
+    /// stops the search from outside (e.g., the game is over)
+    pub stop: StopToken,
+    /// receives the report of every completed iteration
+    pub info: Option<Sender<SearchInfo>>,
+}
+
+/// report of a completed iteration
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchInfo {
+    pub depth: u32,
+    /// value for the side to move
+    pub score: f32,
+    /// principal variation, starting with the best move
+    /// (can be cut short by transposition table hits)
+    pub pv: Vec<Move>,
+    /// nodes per second
+    pub nps: u64,
+    pub elapsed: Duration,
+    /// counters of the search so far (every iteration)
+    pub stats: SearchStats,
+}
+
+/// counters of a single search
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct SearchStats {
+    /// moves searched
+    pub nodes: u64,
+    pub tt_hits: u64,
+    /// alpha-beta cutoffs
+    pub cutoffs: u64,
+    pub eval_calls: u64,
+    /// nodes whose moves are generated
+    pub expanded: u64,
+    /// moves generated by the prune
+    pub candidates: u64,
+    /// deepest height reached from the root
+    pub max_depth: u32,
+    /// moves searched again after the null window or the aspiration window failed
+    pub researches: u64,
+}
+
+impl SearchStats {
+    /// adds the counters of another thread
+    pub fn merge(&mut self, other: &SearchStats) {
+        self.nodes += other.nodes;
+        self.tt_hits += other.tt_hits;
+        self.cutoffs += other.cutoffs;
+        self.eval_calls += other.eval_calls;
+        self.expanded += other.expanded;
+        self.candidates += other.candidates;
+        self.researches += other.researches;
+        self.max_depth = self.max_depth.max(other.max_depth);
+    }
+
+    /// average moves generated per expanded node
+    pub fn branching_factor(&self) -> f64 {
+        if self.expanded == 0 {
+            return 0.0;
+        }
+        self.candidates as f64 / self.expanded as f64
+    }
+}
+
+/// best move and its value for the side to move
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchResult {
+    /// None if there's no move or cancelled in the first iteration
+    pub best: Option<Move>,
+    pub score: f32,
+    /// deepest completed iteration
+    pub depth: u32,
+    pub stats: SearchStats,
+    pub elapsed: Duration,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Clock {
+    pub remaining: Duration,
+    /// time added after each move
+    pub increment: Duration,
+}
+
+pub const MAX_DEPTH: u32 = 64;
+
+// a move spends 1/CLOCK_MOVES of the remaining clock (plus the increment)
+const CLOCK_MOVES: u32 = 20;
+
+impl SearchLimits {
+    /// fixed depth, no time limit
+    pub fn depth(depth: u32) -> Self {
+        Self {
+            depth,
+            move_time: None,
+            clock: None,
+            stop: StopToken::new(),
+            info: None,
+        }
+    }
+
+    pub fn move_time(time: Duration) -> Self {
+        Self {
+            depth: MAX_DEPTH,
+            move_time: Some(time),
+            clock: None,
+            stop: StopToken::new(),
+            info: None,
+        }
+    }
+
+    pub fn clock(remaining: Duration, increment: Duration) -> Self {
+        Self {
+            depth: MAX_DEPTH,
+            move_time: None,
+            clock: Some(Clock { remaining, increment }),
+            stop: StopToken::new(),
+            info: None,
+        }
+    }
+
+    /// time to spend on this move, None if unlimited
+    pub fn budget(&self) -> Option<Duration> {
+        let clock = self.clock.map(|c| {
+            let share = c.remaining / CLOCK_MOVES + c.increment;
+            // keep a margin not to lose on time
+            share.min(c.remaining / 2)
+        });
+        match (self.move_time, clock) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
 }
 
-use std::time::Instant;
-// static variables for checking performance
-use std::sync::atomic::{AtomicU64, Ordering};
-pub static NODE_COUNT: AtomicU64 = AtomicU64::new(0);
-pub static ABP_CUTOFF: AtomicU64 = AtomicU64::new(0);
-pub static TT_HIT: AtomicU64 = AtomicU64::new(0);
+/// cancellation of a running search, shared with another thread
+/// (clones refer to the same flag)
+#[derive(Debug, Clone, Default)]
+pub struct StopToken(Arc<AtomicBool>);
+
+impl StopToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn stop(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
 
 #[derive(Debug)]
 pub struct NegamaxModel<E: Eval, P: Prune, R: Rule> {
-    pub depth: u32,
     pub eval: E,
     pub prune: P,
     pub rule: R,
-    pub zobrist: Zobrist,
-    // pub tt: Mutex<TT>,
+    /// shared by the threads of the search
     pub tt: TT,
+    /// whether the search probes and stores the transposition table
+    pub use_tt: bool,
+    /// principal variation search, moves after the first are tried with a null window
+    pub pvs: bool,
+    /// iterations start with a window around the score of the previous one
+    pub aspiration: bool,
+    /// a victory by continuous fours is searched before the main search
+    pub vcf: bool,
+    /// a victory by continuous threats (fours and threes) is searched too,
+    /// and root moves losing to the threats of the opponent are left out (slow)
+    pub vct: bool,
+    /// threads searching at once (lazy smp), sharing the transposition table,
+    /// the result is reproducible only with a single thread
+    pub threads: usize,
+    // every random choice comes from this, so games can be replayed by the seed
+    seed: u64,
+    rng: StdRng,
+}
+
+/// state of the search on a thread
+#[derive(Debug)]
+struct Worker<'a, E: Eval, P: Prune, R: Rule> {
+    model: &'a NegamaxModel<E, P, R>,
+    /// 0 for the calling thread, which reports and decides the result
+    id: usize,
+    rng: StdRng,
+    // search ends when this passes
+    deadline: Option<Instant>,
+    // search ends when this is stopped
+    stop: StopToken,
+    // set when the search ran out of time or is cancelled,
+    // values are not reliable then
+    stopped: bool,
+    // depth of the current iteration
+    root_depth: u32,
+    // principal variation from each height of the tree (root is 0)
+    pv: Vec<Vec<Move>>,
+    // counters of the current search
+    stats: SearchStats,
 }
 
+// score of the win, minus the plies from the root to prefer faster wins
+const WIN: f32 = 100000.0;
+
+// half width of the aspiration window
+const ASPIRATION: f32 = 50.0;
+
+// the clock is checked once in this many nodes
+const CHECK_INTERVAL: u64 = 1024;
+
+/// seed of the models made by `NegamaxModel::new`
+pub const DEFAULT_SEED: u64 = 0;
+
 impl<E: Eval, P: Prune, R: Rule> NegamaxModel<E, P, R> {
-    pub fn new(depth: u32, eval: E, prune: P, rule: R) -> Self {
+    pub fn new(eval: E, prune: P, rule: R) -> Self {
+        Self::with_seed(eval, prune, rule, DEFAULT_SEED)
+    }
+
+    /// model which makes the same moves for the same seed
+    pub fn with_seed(eval: E, prune: P, rule: R, seed: u64) -> Self {
+        tracing::info!("negamax model seed: {}", seed);
         Self {
-            depth,
             eval,
             prune,
             rule,
-            zobrist: Zobrist::init(),
-            // tt: Mutex::new(TT::new(65536)),
-            tt: TT::new(65536),
+            tt: TT::with_mb(tt::DEFAULT_MB),
+            use_tt: true,
+            pvs: true,
+            aspiration: true,
+            vcf: true,
+            vct: false,
+            threads: 1,
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+impl<'a, E: Eval, P: Prune, R: Rule> Worker<'a, E, P, R> {
+    fn new(model: &'a NegamaxModel<E, P, R>, id: usize, rng: StdRng, stop: StopToken) -> Self {
+        Self {
+            model,
+            id,
+            rng,
+            deadline: None,
+            stopped: stop.is_stopped(),
+            stop,
+            root_depth: 0,
+            pv: vec![Vec::new(); MAX_DEPTH as usize + 2],
+            stats: SearchStats::default(),
         }
     }
 
+    /// best line from the height is mv and then the line of the child
+    fn update_pv(&mut self, height: usize, mv: Move) {
+        let (head, tail) = self.pv.split_at_mut(height + 1);
+        let line = &mut head[height];
+        line.clear();
+        line.push(mv);
+        line.extend_from_slice(&tail[0]);
+    }
+
     fn negamax(
         &mut self, 
         board: &mut Board, 
@@ -50,33 +306,138 @@ impl<E: Eval, P: Prune, R: Rule> NegamaxModel<E, P, R> {
         alpha: f32,
         beta: f32,
         mv: Move, 
-        hash: u64,
     ) -> f32 {
+        // stored value is usable if it's searched at least as deep
+        // and it's exact or out of the window
+        let mut tt_move = None;
+        let hash = board.hash();
+        if let Some(entry) = self.probe(hash, d) {
+            tt_move = entry.best;
+            let usable = entry.depth >= d && match entry.bound {
+                Bound::Exact => true,
+                Bound::Lower => entry.value >= beta,
+                Bound::Upper => entry.value <= alpha,
+            };
+            if usable {
+                self.stats.tt_hits += 1;
+                return entry.value;
+            }
+        }
+
         if d == 0 {
-            return self.eval.eval(&board, mv);
+            self.stats.eval_calls += 1;
+            let value = self.model.eval.eval(board, mv);
+            self.store(hash, value, d, Bound::Exact, None);
+            return value;
         }
 
-        let possible = self.prune.possible(&board, mv);
+        let mut possible = self.model.prune.possible(board, mv);
         if possible.is_empty() {
             // terminal node
-            return self.eval.eval(&board, mv);
+            self.stats.eval_calls += 1;
+            return self.model.eval.eval(board, mv);
+        }
+        self.stats.expanded += 1;
+        self.stats.candidates += possible.len() as u64;
+
+        // best move of the previous search comes first
+        if let Some(i) = tt_move.and_then(|t| possible.iter().position(|&m| m == t)) {
+            possible[..=i].rotate_right(1);
         }
 
-        let mut max = core::f32::NEG_INFINITY;
+        let height = (self.root_depth - d) as usize;
+        let alpha_orig = alpha;
         let mut alpha = alpha;
+        let mut max = f32::NEG_INFINITY;
+        let mut best = None;
         for mv in possible {
-            let eval = self.eval_after_move(board, d, alpha, beta, mv, hash);
-            max = max.max(eval);
+            let eval = if self.model.pvs && best.is_some() {
+                self.scout(board, d, alpha, beta, mv)
+            } else {
+                self.eval_after_move(board, d, alpha, beta, mv)
+            };
+            if self.stopped {
+                return 0.0;
+            }
+            if eval > alpha {
+                self.update_pv(height, mv);
+            }
+            if eval > max || best.is_none() {
+                max = eval;
+                best = Some(mv);
+            }
             alpha = alpha.max(eval);
             if alpha >= beta {
-                ABP_CUTOFF.fetch_add(1, Ordering::Relaxed);
+                self.stats.cutoffs += 1;
                 break;
             }
         }
 
+        let bound = if max <= alpha_orig {
+            Bound::Upper
+        } else if max >= beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        self.store(hash, max, d, bound, best);
+
         max
     }
 
+    /// tries the move with a null window above alpha,
+    /// and with the full window only if it can be better than alpha
+    fn scout(&mut self, board: &mut Board, d: u32, alpha: f32, beta: f32, mv: Move) -> f32 {
+        let eval = self.eval_after_move(board, d, alpha, alpha.next_up(), mv);
+        if eval > alpha && eval < beta && !self.stopped {
+            self.stats.researches += 1;
+            return self.eval_after_move(board, d, alpha, beta, mv);
+        }
+        eval
+    }
+
+    /// entry of the position at the remaining depth `d`
+    fn probe(&mut self, hash: u64, d: u32) -> Option<TTEntry> {
+        if !self.model.use_tt {
+            return None;
+        }
+        let mut entry = self.model.tt.get(hash)?;
+        entry.value = self.to_root(entry.value, d);
+        Some(entry)
+    }
+
+    /// `depth` is the remaining depth searched below the position,
+    /// wins are stored as plies from the position so they can be reused from any path
+    fn store(&mut self, hash: u64, value: f32, depth: u32, bound: Bound, best: Option<Move>) {
+        if self.model.use_tt {
+            let value = self.to_node(value, depth);
+            self.model.tt.put(TTEntry { hash, value, depth, bound, best });
+        }
+    }
+
+    /// wins counted from the position at the remaining depth `d`, not from the root
+    fn to_node(&self, value: f32, d: u32) -> f32 {
+        let height = (self.root_depth - d) as f32;
+        if value >= WIN - MAX_DEPTH as f32 {
+            value + height
+        } else if value <= -(WIN - MAX_DEPTH as f32) {
+            value - height
+        } else {
+            value
+        }
+    }
+
+    fn to_root(&self, value: f32, d: u32) -> f32 {
+        let height = (self.root_depth - d) as f32;
+        if value >= WIN - MAX_DEPTH as f32 {
+            value - height
+        } else if value <= -(WIN - MAX_DEPTH as f32) {
+            value + height
+        } else {
+            value
+        }
+    }
+
     // helper function (common logic)
     fn eval_after_move(
         &mut self, 
@@ -85,100 +446,683 @@ impl<E: Eval, P: Prune, R: Rule> NegamaxModel<E, P, R> {
         alpha: f32,
         beta: f32,
         mv: Move, 
-        hash: u64,
     ) -> f32 {
-        NODE_COUNT.fetch_add(1, Ordering::Relaxed);
+        self.stats.nodes += 1;
+        if self.stats.nodes.is_multiple_of(CHECK_INTERVAL) {
+            let timeout = self.deadline.is_some_and(|d| Instant::now() >= d);
+            self.stopped = timeout || self.stop.is_stopped();
+        }
+        if self.stopped {
+            return 0.0;
+        }
+        // line of the child is filled only if it's searched
+        let height = self.root_depth - d + 1;
+        self.pv[height as usize].clear();
+        self.stats.max_depth = self.stats.max_depth.max(height);
 
         let turn = board.turn();
 
-        // update hash value
-        let hash = self.zobrist.update(hash, mv, turn.to_stone());
-        let depth = board.ply() + d;
-        if let Some(entry) = self.tt.get(hash) {
-            if entry.depth >= depth {
-                TT_HIT.fetch_add(1, Ordering::Relaxed);
-                return entry.value;
-            }
-
-            // todo: 
-            // add best_move to ttentry and search that move first
-        }
-
-        let result = self.rule.put(board, mv, turn);
-        let eval = match result {
+        let result = self.model.rule.put(board, mv, turn);
+        match result {
             Ok(outcome) => {
                 let value = match outcome {
                     PutOutcome::Continue => -self.negamax(
-                        board, d - 1, -beta, -alpha, mv, hash
+                        board, d - 1, -beta, -alpha, mv
                     ),
-                    PutOutcome::Win => 100000.0 - d as f32,
+                    PutOutcome::Win => WIN - height as f32,
                     PutOutcome::Draw => 0.0,
                 };
 
                 // revert to previous state
                 board.undo_unchecked(mv);
 
+                if self.stopped {
+                    return 0.0;
+                }
                 value
             },
-            Err(error_type) => {
+            Err(_error_type) => {
                 // tracing::debug!("{:?}", error_type);
                 // invalid moves (e.g., forbidden like 3-3) are treated as worst possible
-                core::f32::NEG_INFINITY
+                f32::NEG_INFINITY
             }
+        }
+    }
+}
+
+// score within this is regarded as balanced position when choosing color
+const BALANCED: f32 = 50.0;
+// vcf and vct solvers before the search get 1/SOLVER_SHARE of the time
+const SOLVER_SHARE: u32 = 4;
+
+impl<E: Eval, P: Prune, R: Rule> NegamaxModel<E, P, R> {
+    /// best move and its value for the side to move,
+    /// deepening the search until the limits
+    pub fn search(&mut self, board: &Board, mv: Move, limits: SearchLimits) -> SearchResult {
+        // entries of the previous searches are replaced first
+        self.tt.new_generation();
+
+        // start timer
+        let start = Instant::now();
+
+        // the solvers take a share of the time, the search needs the rest
+        let deadline = limits.budget().map(|b| start + b / SOLVER_SHARE);
+        let mut vcf = Vcf::new(&self.rule);
+        vcf.budget = vcf.budget.until(deadline, limits.stop.clone());
+        let mut vct = Vct::new(&self.rule);
+        vct.budget = vct.budget.until(deadline, limits.stop.clone());
+        let line = self.vcf.then(|| vcf.solve(board)).flatten()
+            .or_else(|| self.vct.then(|| vct.solve(board)).flatten());
+        if let Some(line) = line {
+            return self.search_line(board, line, &limits, start);
+        }
+        let roots = if self.vct { self.defences(board, mv, &vct) } else { None };
+
+        let model = &*self;
+        // helpers search until the calling thread is done
+        let done = StopToken::new();
+        let (result, rng) = thread::scope(|scope| {
+            let helpers: Vec<_> = (1..model.threads.max(1))
+                .map(|id| {
+                    let rng = StdRng::seed_from_u64(model.seed.wrapping_add(id as u64));
+                    let mut worker = Worker::new(model, id, rng, done.clone());
+                    let limits = &limits;
+                    let roots = roots.as_deref();
+                    scope.spawn(move || {
+                        worker.deepen(board, mv, roots, limits, start);
+                        worker.stats
+                    })
+                })
+                .collect();
+
+            let mut main = Worker::new(model, 0, model.rng.clone(), limits.stop.clone());
+            let (best, score, depth) = main.deepen(board, mv, roots.as_deref(), &limits, start);
+            done.stop();
+
+            let mut stats = main.stats;
+            for helper in helpers {
+                stats.merge(&helper.join().expect("search thread panicked"));
+            }
+            let result = SearchResult {
+                best,
+                score,
+                depth,
+                stats,
+                elapsed: start.elapsed(),
+            };
+            (result, main.rng)
+        });
+        // the next search continues the random sequence
+        self.rng = rng;
+
+        tracing::debug!(
+            "{:?}, branching factor: {:.2}",
+            result, result.stats.branching_factor(),
+        );
+        tracing::debug!(
+            "{:?}, hit rate: {:.3}, usage: {}/1000",
+            self.tt.stats(), self.tt.stats().hit_rate(), self.tt.usage(),
+        );
+
+        result
+    }
+
+    /// result of the winning line of vcf or vct, the search is not needed then
+    fn search_line(
+        &self, board: &Board, line: Vec<Move>, limits: &SearchLimits, start: Instant
+    ) -> SearchResult {
+        // the line ends with five, with a four which wins in two plies,
+        // or with threes which win in four plies
+        let mut next = *board;
+        let mut outcome = None;
+        for &mv in &line {
+            let turn = next.turn();
+            outcome = self.rule.put(&mut next, mv, turn).ok();
+        }
+        let fives = vcf::five_points(&self.rule, &next, vcf::empty_cells(&next), board.turn());
+        let plies = match outcome {
+            Some(PutOutcome::Win) => line.len(),
+            _ if fives.is_empty() => line.len() + 4,
+            _ => line.len() + 2,
         };
 
-        let entry = TTEntry {
-            hash,
-            value: eval,
-            depth,
+        let elapsed = start.elapsed();
+        let info = SearchInfo {
+            depth: plies as u32,
+            score: WIN - plies as f32,
+            pv: line.clone(),
+            nps: 0,
+            elapsed,
+            stats: SearchStats::default(),
         };
-        self.tt.put(entry);
+        tracing::debug!("winning line: {:?}", info);
+        if let Some(tx) = &limits.info {
+            let _ = tx.send(info);
+        }
 
-        eval
+        SearchResult {
+            best: line.first().copied(),
+            score: WIN - plies as f32,
+            depth: plies as u32,
+            stats: SearchStats::default(),
+            elapsed,
+        }
     }
-}
 
-impl<E: Eval, P: Prune, R: Rule> Model for NegamaxModel<E, P, R> {
-    fn next_move(&mut self, board: &Board, mv: Move) -> Option<Move> {
-        // reset performance counter
-        NODE_COUNT.store(0, Ordering::Relaxed);
-        ABP_CUTOFF.store(0, Ordering::Relaxed);
-        TT_HIT.store(0, Ordering::Relaxed);
+    /// root moves after which the opponent has no vct,
+    /// None if the opponent has no vct now (or every move loses to it)
+    ///
+    /// every solve ends at the deadline of `vct`,
+    /// moves left unsolved after that are kept
+    fn defences(&self, board: &Board, mv: Move, vct: &Vct<R>) -> Option<Vec<Move>> {
+        let mut passed = *board;
+        passed.pass();
+        vct.solve(&passed)?;
 
-        // start timer
-        let start = Instant::now();
+        let turn = board.turn();
+        let mut next = *board;
+        let moves: Vec<Move> = self.prune.possible(board, mv)
+            .into_iter()
+            .filter(|&m| match self.rule.put(&mut next, m, turn) {
+                Ok(PutOutcome::Continue) => {
+                    let safe = vct.solve(&next).is_none();
+                    next.undo_unchecked(m);
+                    safe
+                },
+                Ok(_) => {
+                    next.undo_unchecked(m);
+                    true
+                },
+                Err(_) => false,
+            })
+            .collect();
+        tracing::debug!("moves against the vct of the opponent: {:?}", moves);
+        (!moves.is_empty()).then_some(moves)
+    }
+}
+
+impl<E: Eval, P: Prune, R: Rule> Worker<'_, E, P, R> {
+    /// iterative deepening until the limits, returns the best move,
+    /// its value and the depth of the last completed iteration
+    /// (`roots`: moves searched instead of the candidates of the prune)
+    fn deepen(
+        &mut self, board: &Board, mv: Move, roots: Option<&[Move]>, limits: &SearchLimits,
+        start: Instant,
+    ) -> (Option<Move>, f32, u32) {
+        // helpers are stopped by the calling thread
+        let budget = limits.budget().filter(|_| self.id == 0);
 
         let mut best = f32::NEG_INFINITY;
         let mut best_mv = None;
+        let mut depth = 0;
 
         // start point of simulation
-        let mut clone_board = board.clone();
+        let mut clone_board = *board;
 
-        // calculate hash
-        let hash = self.zobrist.hash(board);
+        let mut moves = match roots {
+            Some(roots) => roots.to_vec(),
+            None => self.model.prune.possible(board, mv),
+        };
+        // half of the helpers start a ply deeper, to fill the table ahead of the others
+        let max_depth = limits.depth.max(1);
+        let first = (1 + self.id as u32 % 2).min(max_depth);
+        for d in first..=max_depth {
+            // the first iteration always completes to have a move,
+            // unless the search is cancelled
+            self.deadline = budget.filter(|_| d > 1).map(|b| start + b);
+            self.root_depth = d;
+
+            // narrow window around the previous score,
+            // the real value is unknown if it's out of the window
+            let (alpha, beta) = match best_mv {
+                Some(_) if self.model.aspiration && best.abs() < WIN - MAX_DEPTH as f32 => {
+                    (best - ASPIRATION, best + ASPIRATION)
+                },
+                _ => (f32::NEG_INFINITY, f32::INFINITY),
+            };
+            let mut result = self.search_root(&mut clone_board, &moves, d, alpha, beta);
+            if result.is_some_and(|(_, v)| v <= alpha || v >= beta) && !self.stopped {
+                self.stats.researches += 1;
+                result = self.search_root(
+                    &mut clone_board, &moves, d, f32::NEG_INFINITY, f32::INFINITY
+                );
+            }
+
+            // every candidate can be forbidden (e.g., pro opening rule)
+            // then search empty cells, from the nearest ring of the center
+            let mut r = 0;
+            while result.is_none() && !self.stopped && r <= board.size() / 2 {
+                moves = prune::ring(board, board.center(), r);
+                result = self.search_root(
+                    &mut clone_board, &moves, d, f32::NEG_INFINITY, f32::INFINITY
+                );
+                r += 1;
+            }
+
+            // the iteration ran out of time, keep the previous one
+            // (no move if cancelled in the first iteration)
+            if self.stopped {
+                break;
+            }
+            let Some((mv, value)) = result else {
+                break;
+            };
+            best = value;
+            best_mv = Some(mv);
+            depth = d;
+            if self.id == 0 {
+                self.report(limits, d, value, start.elapsed());
+            }
+
+            // search the best move first in the next iteration
+            if let Some(i) = moves.iter().position(|&m| m == mv) {
+                moves[..=i].rotate_right(1);
+            }
+
+            // the game is decided, deeper search changes nothing
+            if value.abs() >= WIN - MAX_DEPTH as f32 {
+                break;
+            }
+            // next iteration would not end in time
+            if budget.is_some_and(|b| start.elapsed() * 2 > b) {
+                break;
+            }
+        }
+
+        (best_mv, best, depth)
+    }
+
+    /// best of the moves at the depth, None if every move is invalid
+    /// (moves of the same value are picked at random)
+    ///
+    /// the value is exact only within the window
+    fn search_root(
+        &mut self, board: &mut Board, moves: &[Move], d: u32, alpha: f32, beta: f32
+    ) -> Option<(Move, f32)> {
+        let mut best = f32::NEG_INFINITY;
+        let mut best_mv = None;
+        let mut ties = 0;
+        for &mv in moves {
+            let eval = if self.model.pvs && best_mv.is_some() {
+                // values equal to the best are needed for tie breaking
+                self.scout(board, d, best.next_down(), beta, mv)
+            } else {
+                self.eval_after_move(board, d, alpha, beta, mv)
+            };
+            if self.stopped {
+                return None;
+            }
 
-        let possible = self.prune.possible(board, mv);
-        for mv in possible {
-            let eval = self.eval_after_move(
-                &mut clone_board, self.depth, 
-                core::f32::NEG_INFINITY, core::f32::INFINITY, mv, hash,
-            );
-            
             if eval > best {
                 best = eval;
                 best_mv = Some(mv);
+                ties = 1;
+                self.update_pv(0, mv);
+            } else if eval == best && best_mv.is_some() {
+                // each of the tied moves is kept with the same chance
+                ties += 1;
+                if self.rng.gen_range(0..ties) == 0 {
+                    best_mv = Some(mv);
+                    self.update_pv(0, mv);
+                }
+            }
+            // failed high, searched again with the full window
+            if best >= beta {
+                break;
             }
         }
+        best_mv.map(|mv| (mv, best))
+    }
 
-        // record result
-        tracing::debug!(
-            "\nNODE_COUNT: {}\nABP_CUTOFF: {}\nTT_HIT: {}",
-            NODE_COUNT.load(Ordering::Relaxed),
-            ABP_CUTOFF.load(Ordering::Relaxed),
-            TT_HIT.load(Ordering::Relaxed),
-        );
-        tracing::debug!("elapsed: {:?}", start.elapsed());
+    /// sends the result of the iteration
+    fn report(&self, limits: &SearchLimits, depth: u32, score: f32, elapsed: Duration) {
+        let info = SearchInfo {
+            depth,
+            score,
+            pv: self.pv[0].clone(),
+            nps: (self.stats.nodes as f64 / elapsed.as_secs_f64().max(1e-6)) as u64,
+            elapsed,
+            stats: self.stats,
+        };
+        tracing::debug!("{:?}", info);
+
+        if let Some(tx) = &limits.info {
+            // nobody may listen anymore
+            let _ = tx.send(info);
+        }
+    }
+}
+
+impl<E: Eval, P: Prune, R: Rule> Model for NegamaxModel<E, P, R> {
+    fn next_move(&mut self, board: &Board, mv: Move, limits: SearchLimits) -> Option<Move> {
+        self.search(board, mv, limits).best
+    }
+
+    fn choose(
+        &mut self, board: &Board, mv: Move, options: &[Choice], limits: SearchLimits
+    ) -> Choice {
+        // value for the side to move (white) after the choice
+        let value = self.search(board, mv, limits).score;
+
+        if value.abs() < BALANCED && options.contains(&Choice::PutTwo) {
+            // let the opponent choose from a balanced position
+            return Choice::PutTwo;
+        }
+
+        let choice = if value >= 0.0 { Choice::White } else { Choice::Black };
+        tracing::debug!("value: {}, choice: {:?}", value, choice);
+        choice
+    }
+}
+
+
+/// proved value of a position for the side to move
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Proof {
+    Win,
+    Loss,
+    /// not proved within the budget (or drawn)
+    Unknown,
+}
+
+/// result of a proof
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProofResult {
+    pub proof: Proof,
+    /// winning first move if proved a win,
+    /// otherwise the most promising one (the least proof number)
+    pub best: Option<Move>,
+    /// positions in the proof tree, 0 if nothing is proved
+    pub tree_size: usize,
+    /// positions expanded
+    pub nodes: u64,
+    pub elapsed: Duration,
+}
+
+/// Depth-first proof number search (df-pn).
+/// Proves the side to move wins or loses, trying the candidates of the prune,
+/// with a transposition table keyed by the zobrist hash of the board.
+/// Only the time and the stop of the limits are used, not the depth.
+#[derive(Debug)]
+pub struct ProofNumberModel<P: Prune, R: Rule> {
+    pub prune: P,
+    pub rule: R,
+    /// positions expanded at most in a proof
+    pub max_nodes: u64,
+    /// entries of the transposition table at most (memory budget)
+    pub max_entries: usize,
+}
 
-        best_mv
+/// positions expanded by `ProofNumberModel::new`
+pub const DEFAULT_PN_NODES: u64 = 100_000;
+/// table entries of `ProofNumberModel::new` (16 bytes each without the map overhead)
+pub const DEFAULT_PN_ENTRIES: usize = 1 << 20;
+
+// proof and disproof numbers of decided positions
+const PN_INF: u32 = u32::MAX / 2;
+
+// state of a single proof
+struct Prover<'a, P: Prune, R: Rule> {
+    model: &'a ProofNumberModel<P, R>,
+    // side trying to win, the other side defends
+    attacker: Player,
+    // (proof, disproof) numbers of searched positions
+    table: HashMap<u64, (u32, u32)>,
+    nodes: u64,
+    max_nodes: u64,
+    deadline: Option<Instant>,
+    stop: StopToken,
+    // set when a budget ran out, numbers are not final then
+    exhausted: bool,
+}
+
+// a move and the position after it
+struct Child {
+    mv: Move,
+    hash: u64,
+    // numbers of the game end by the move, None if the game continues
+    end: Option<(u32, u32)>,
+}
+
+impl<P: Prune, R: Rule> ProofNumberModel<P, R> {
+    pub fn new(prune: P, rule: R) -> Self {
+        Self {
+            prune,
+            rule,
+            max_nodes: DEFAULT_PN_NODES,
+            max_entries: DEFAULT_PN_ENTRIES,
+        }
+    }
+
+    /// proves the side to move wins, or else the opponent wins,
+    /// until the budget or the limits
+    pub fn prove(&self, board: &Board, mv: Move, limits: &SearchLimits) -> ProofResult {
+        let start = Instant::now();
+        let deadline = limits.budget().map(|b| start + b);
+        let turn = board.turn();
+        let mut board = *board;
+
+        let mut win = Prover::new(self, turn, self.max_nodes, deadline, limits.stop.clone());
+        let (pn, dn) = win.run(&mut board, mv);
+        let mut nodes = win.nodes;
+
+        // the least proof number is the proved win if any
+        let best = win.children(&mut board, mv)
+            .iter()
+            .min_by_key(|child| win.numbers(child).0)
+            .map(|child| child.mv);
+        let win_size = (pn == 0).then(|| win.tree_size(&mut board, mv, &mut HashSet::new()));
+        // free the table before the second prover fills its own
+        drop(win);
+
+        let (proof, tree_size) = if let Some(size) = win_size {
+            (Proof::Win, size)
+        } else if dn == 0 {
+            // no win, the opponent may have one
+            let budget = self.max_nodes.saturating_sub(nodes);
+            let mut loss = Prover::new(self, turn.next(), budget, deadline, limits.stop.clone());
+            let (pn, _) = loss.run(&mut board, mv);
+            nodes += loss.nodes;
+            if pn == 0 {
+                (Proof::Loss, loss.tree_size(&mut board, mv, &mut HashSet::new()))
+            } else {
+                (Proof::Unknown, 0)
+            }
+        } else {
+            (Proof::Unknown, 0)
+        };
+
+        let result = ProofResult {
+            proof,
+            best,
+            tree_size,
+            nodes,
+            elapsed: start.elapsed(),
+        };
+        tracing::debug!("{:?}", result);
+
+        if let Some(tx) = &limits.info {
+            let score = match proof {
+                Proof::Win => WIN,
+                Proof::Loss => -WIN,
+                Proof::Unknown => 0.0,
+            };
+            let info = SearchInfo {
+                depth: 0,
+                score,
+                pv: best.into_iter().collect(),
+                nps: (nodes as f64 / result.elapsed.as_secs_f64().max(1e-6)) as u64,
+                elapsed: result.elapsed,
+                stats: SearchStats { nodes, ..SearchStats::default() },
+            };
+            let _ = tx.send(info);
+        }
+
+        result
+    }
+}
+
+impl<'a, P: Prune, R: Rule> Prover<'a, P, R> {
+    fn new(
+        model: &'a ProofNumberModel<P, R>, attacker: Player, max_nodes: u64,
+        deadline: Option<Instant>, stop: StopToken,
+    ) -> Self {
+        Self {
+            model,
+            attacker,
+            table: HashMap::new(),
+            nodes: 0,
+            max_nodes,
+            deadline,
+            stop,
+            exhausted: false,
+        }
+    }
+
+    /// numbers of the position when proved, disproved or out of the budget
+    fn run(&mut self, board: &mut Board, last: Move) -> (u32, u32) {
+        self.mid(board, last, PN_INF, PN_INF)
+    }
+
+    /// searches the position until its numbers reach the thresholds
+    fn mid(&mut self, board: &mut Board, last: Move, thpn: u32, thdn: u32) -> (u32, u32) {
+        self.nodes += 1;
+        if self.nodes > self.max_nodes
+            || self.table.len() >= self.model.max_entries
+            || self.stop.is_stopped()
+            || self.deadline.is_some_and(|d| Instant::now() >= d)
+        {
+            self.exhausted = true;
+        }
+
+        let children = self.children(board, last);
+        // the attacker needs one winning move, the defender loses with every move
+        let or = board.turn() == self.attacker;
+        loop {
+            let numbers = children.iter().map(|child| self.numbers(child));
+            let (pn, dn) = if or {
+                let pn = numbers.clone().map(|(pn, _)| pn).min().unwrap_or(PN_INF);
+                let dn = numbers.map(|(_, dn)| dn).fold(0, add);
+                (pn, dn)
+            } else {
+                let pn = numbers.clone().map(|(pn, _)| pn).fold(0, add);
+                let dn = numbers.map(|(_, dn)| dn).min().unwrap_or(PN_INF);
+                (pn, dn)
+            };
+            if pn >= thpn || dn >= thdn || self.exhausted {
+                self.table.insert(board.hash(), (pn, dn));
+                return (pn, dn);
+            }
+
+            // the most proving child, and the threshold to switch to the second one
+            let mut order: Vec<(u32, usize)> = children.iter()
+                .enumerate()
+                .map(|(i, child)| {
+                    let (cpn, cdn) = self.numbers(child);
+                    (if or { cpn } else { cdn }, i)
+                })
+                .collect();
+            order.sort_unstable();
+            let second = order.get(1).map_or(PN_INF, |&(n, _)| n);
+            let child = &children[order[0].1];
+            let (cpn, cdn) = self.numbers(child);
+            let (tpn, tdn) = if or {
+                (thpn.min(add(second, 1)), thdn - dn + cdn)
+            } else {
+                (thpn - pn + cpn, thdn.min(add(second, 1)))
+            };
+
+            let turn = board.turn();
+            board.put(child.mv, turn.to_stone());
+            self.mid(board, child.mv, tpn, tdn);
+            board.undo_unchecked(child.mv);
+        }
+    }
+
+    /// numbers of the position after the child move
+    fn numbers(&self, child: &Child) -> (u32, u32) {
+        child.end
+            .or_else(|| self.table.get(&child.hash).copied())
+            .unwrap_or((1, 1))
+    }
+
+    /// legal candidate moves, the numbers are decided if the game ends by them
+    fn children(&self, board: &mut Board, last: Move) -> Vec<Child> {
+        let turn = board.turn();
+        let mut candidates = self.model.prune.possible(board, last);
+        // every candidate can be forbidden, then any empty cell
+        if candidates.iter().all(|&mv| !self.model.rule.is_valid(board, mv, turn)) {
+            candidates = vcf::empty_cells(board).collect();
+        }
+
+        let mut children = Vec::new();
+        for mv in candidates {
+            let Ok(outcome) = self.model.rule.put(board, mv, turn) else {
+                continue;
+            };
+            let end = match outcome {
+                PutOutcome::Continue => None,
+                PutOutcome::Win if turn == self.attacker => Some((0, PN_INF)),
+                // a draw is not a win of the attacker
+                _ => Some((PN_INF, 0)),
+            };
+            children.push(Child { mv, hash: board.hash(), end });
+            board.undo_unchecked(mv);
+        }
+        children
+    }
+
+    /// positions in the proof tree from the proved position,
+    /// one winning move of the attacker and every move of the defender
+    fn tree_size(&self, board: &mut Board, last: Move, seen: &mut HashSet<u64>) -> usize {
+        if !seen.insert(board.hash()) {
+            return 0;
+        }
+        let or = board.turn() == self.attacker;
+        let mut size = 1;
+        for child in self.children(board, last) {
+            if self.numbers(&child).0 != 0 {
+                continue;
+            }
+            if child.end.is_some() {
+                size += 1;
+            } else {
+                let turn = board.turn();
+                board.put(child.mv, turn.to_stone());
+                size += self.tree_size(board, child.mv, seen);
+                board.undo_unchecked(child.mv);
+            }
+            if or {
+                break;
+            }
+        }
+        size
+    }
+}
+
+/// sum of proof (or disproof) numbers, saturating at the infinity
+fn add(a: u32, b: u32) -> u32 {
+    a.saturating_add(b).min(PN_INF)
+}
+
+impl<P: Prune, R: Rule> Model for ProofNumberModel<P, R> {
+    fn next_move(&mut self, board: &Board, mv: Move, limits: SearchLimits) -> Option<Move> {
+        self.prove(board, mv, &limits).best
+    }
+
+    fn choose(
+        &mut self, board: &Board, mv: Move, options: &[Choice], limits: SearchLimits
+    ) -> Choice {
+        // proof for the side to move (white) after the choice
+        let proof = self.prove(board, mv, &limits).proof;
+        let choice = match proof {
+            Proof::Win => Choice::White,
+            Proof::Loss => Choice::Black,
+            // nothing is proved, let the opponent choose
+            Proof::Unknown if options.contains(&Choice::PutTwo) => Choice::PutTwo,
+            Proof::Unknown => Choice::White,
+        };
+        tracing::debug!("proof: {:?}, choice: {:?}", proof, choice);
+        choice
     }
 }
\ No newline at end of file
@@ -1,5 +1,6 @@
 use std::fmt::Debug;
-use crate::core::board::{Board, Move, Stone};
+use crate::core::board::{Board, Move, Stone, MAX_SIZE};
+use crate::core::pattern::Direction;
 
 pub trait Prune: Debug + Send + Sync {
     fn possible(&self, board: &Board, mv: Move) -> Vec<Move>;
@@ -8,53 +9,49 @@ pub trait Prune: Debug + Send + Sync {
 #[derive(Debug)]
 pub struct NeighborPrune;
 
-const SHIFT_ARRAY: [(i32, i32); 8] = [(1,1),(1,0),(1,-1),(0,-1),(-1,-1),(-1,0),(-1,1),(0,1)];
 impl Prune for NeighborPrune {
     fn possible(&self, board: &Board, mv: Move) -> Vec<Move> {
-        let _ = mv; // unused
-        let mut candid = [[false; 15]; 15];
-        for i in 0..15 {
-            for j in 0..15 {
-                let mv = Move::new(j, i).unwrap();
-                if board.get(mv) == Stone::None {
-                    continue;
-                }
-                for (dx, dy) in SHIFT_ARRAY {
-                    let x = j as i32 - dx;
-                    let y = i as i32 - dy;
+        let size = board.size();
 
-                    if x < 0 || y < 0 || x >= 15 || y >= 15 {
-                        continue;
-                    }
-                    candid[y as usize][x as usize] = true;
-                }
-            }
+        // rows as bitsets, neighbors are the stones spread by one cell
+        let mut empty = [0u32; MAX_SIZE];
+        let mut spread = [0u32; MAX_SIZE];
+        for y in 0..size {
+            let row = board.line_bits(Move { x: 0, y }, Direction::Horizontal);
+            let occupied = row.black | row.white;
+            empty[y] = row.stones(Stone::None);
+            spread[y] = occupied | occupied << 1 | occupied >> 1;
         }
 
         let mut v = Vec::new();
-        for i in 0..15 {
-            for j in 0..15 {
-                if !candid[i][j] {
-                    continue;
-                }
-                let mv = Move::new(j, i).unwrap();
-                if board.get(mv) == Stone::None {
-                    v.push(mv);
-                }
+        for y in 0..size {
+            let mut candid = spread[y];
+            if y > 0 {
+                candid |= spread[y - 1];
+            }
+            if y + 1 < size {
+                candid |= spread[y + 1];
+            }
+            candid &= empty[y];
+
+            while candid != 0 {
+                let x = candid.trailing_zeros() as usize;
+                v.push(Move { x, y });
+                candid &= candid - 1;
             }
         }
 
         // heuristic: consider distance from last put position and center
         v.sort_by_key(|item| {
             let dist_last = chebyshev_dist(mv, *item);
-            let dist_center = chebyshev_dist(Move { x: 7, y: 7}, *item);
+            let dist_center = chebyshev_dist(board.center(), *item);
             dist_last * 10 + dist_center
         });
 
-        // if there's nowhere to put (assuming blank board)
-        // or full-filled board can be the case
-        if v.is_empty() {
-            vec![Move { x: 7, y: 7 }]
+        // if there's nowhere to put on blank board, start from center
+        // full-filled board has no possible move (terminal node)
+        if v.is_empty() && board.ply() == 0 {
+            vec![board.center()]
         } else {
             v
         }
@@ -62,6 +59,20 @@ impl Prune for NeighborPrune {
 }
 
 
+/// empty cells at the distance r from the center
+pub fn ring(board: &Board, center: Move, r: usize) -> Vec<Move> {
+    let mut v = Vec::new();
+    for y in 0..board.size() {
+        for x in 0..board.size() {
+            let mv = Move { x, y };
+            if chebyshev_dist(center, mv) == r && board.get(mv) == Stone::None {
+                v.push(mv);
+            }
+        }
+    }
+    v
+}
+
 // utils
 fn chebyshev_dist(mv1: Move, mv2: Move) -> usize {
     let dx = mv1.x.abs_diff(mv2.x);
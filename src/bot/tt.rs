@@ -1,40 +1,248 @@
+use std::fmt::{self, Debug};
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
 use crate::core::board::Move;
 
+/// how the stored value relates to the real value
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Bound {
+    Exact,
+    /// value failed high, the real value is at least this
+    Lower,
+    /// value failed low, the real value is at most this
+    Upper,
+}
+
 #[derive(Copy, Clone, Debug)]
 pub struct TTEntry {
     pub hash: u64,
     pub value: f32,
     pub depth: u32,
+    pub bound: Bound,
+    /// move to search first, None at leaves
+    pub best: Option<Move>,
+}
+
+/// entries sharing an index, one of them is replaced when full
+const BUCKET_SIZE: usize = 4;
+
+/// an entry loses this much depth per search it has survived
+const AGE_WEIGHT: i32 = 2;
+
+pub const DEFAULT_MB: usize = 16;
+
+/// entry packed into a word, the key is stored xor the data
+/// so an entry torn by threads writing at once never matches
+/// (empty if the data is 0)
+#[derive(Default)]
+struct Slot {
+    key: AtomicU64,
+    data: AtomicU64,
+}
+
+type Bucket = [Slot; BUCKET_SIZE];
+
+// bit layout of the data
+// value: 0..32, depth: 32..40, bound: 40..42, best: 42..53, generation: 53..61
+const DEPTH_SHIFT: u32 = 32;
+const BOUND_SHIFT: u32 = 40;
+const BEST_SHIFT: u32 = 42;
+const GENERATION_SHIFT: u32 = 53;
+const OCCUPIED: u64 = 1 << 61;
+
+fn pack(e: &TTEntry, generation: u8) -> u64 {
+    let bound = match e.bound {
+        Bound::Exact => 0,
+        Bound::Lower => 1,
+        Bound::Upper => 2,
+    };
+    // (1, x, y) in 11 bits, 0 if there's no move
+    let best = e.best.map_or(0, |mv| 1 | (mv.x as u64) << 1 | (mv.y as u64) << 6);
+    e.value.to_bits() as u64
+        | (e.depth.min(u8::MAX as u32) as u64) << DEPTH_SHIFT
+        | bound << BOUND_SHIFT
+        | best << BEST_SHIFT
+        | (generation as u64) << GENERATION_SHIFT
+        | OCCUPIED
+}
+
+fn unpack(hash: u64, data: u64) -> TTEntry {
+    let bound = match (data >> BOUND_SHIFT) & 3 {
+        0 => Bound::Exact,
+        1 => Bound::Lower,
+        _ => Bound::Upper,
+    };
+    let best = (data >> BEST_SHIFT) & 0x7ff;
+    TTEntry {
+        hash,
+        value: f32::from_bits(data as u32),
+        depth: ((data >> DEPTH_SHIFT) & 0xff) as u32,
+        bound,
+        best: (best & 1 != 0).then_some(Move {
+            x: ((best >> 1) & 0x1f) as usize,
+            y: ((best >> 6) & 0x1f) as usize,
+        }),
+    }
+}
+
+fn generation_of(data: u64) -> u8 {
+    (data >> GENERATION_SHIFT) as u8
+}
+
+/// counters for tuning the table size and replacement
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct TTStats {
+    pub probes: u64,
+    pub hits: u64,
+    pub stores: u64,
+    /// stores which evicted an entry of another position
+    pub collisions: u64,
+}
+
+impl TTStats {
+    pub fn hit_rate(&self) -> f64 {
+        if self.probes == 0 {
+            0.0
+        } else {
+            self.hits as f64 / self.probes as f64
+        }
+    }
 }
 
-#[derive(Debug)]
+/// transposition table shared by the threads of a search,
+/// entries are read and written without locks
 pub struct TT {
-    table: Vec<Option<TTEntry>>,
-    divisor: usize
+    buckets: Vec<Bucket>,
+    divisor: usize,
+    generation: AtomicU8,
+    probes: AtomicU64,
+    hits: AtomicU64,
+    stores: AtomicU64,
+    collisions: AtomicU64,
+}
+
+impl Debug for TT {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TT")
+            .field("capacity", &self.capacity())
+            .field("stats", &self.stats())
+            .finish()
+    }
 }
 
 impl TT {
-    /// the size should be 2^n
-    pub fn new(size: usize) -> Self {
+    /// table using at most `mb` megabytes (at least one bucket)
+    pub fn with_mb(mb: usize) -> Self {
+        let count = mb * 1024 * 1024 / size_of::<Bucket>();
+        // round down to 2^n
+        let count = match count {
+            0 => 1,
+            n => 1 << n.ilog2(),
+        };
         Self {
-            table: vec![None; size],
-            divisor: size - 1,
+            buckets: (0..count).map(|_| Bucket::default()).collect(),
+            divisor: count - 1,
+            generation: AtomicU8::new(0),
+            probes: AtomicU64::new(0),
+            hits: AtomicU64::new(0),
+            stores: AtomicU64::new(0),
+            collisions: AtomicU64::new(0),
         }
     }
 
-    pub fn get(&self, hash: u64) -> Option<TTEntry> {
-        let idx = hash as usize & self.divisor;
-        match self.table[idx] {
-            Some(e) if e.hash == hash => Some(e),
-            _ => None
+    /// number of entries the table can hold
+    pub fn capacity(&self) -> usize {
+        self.buckets.len() * BUCKET_SIZE
+    }
+
+    /// marks entries stored so far as old, call once per search
+    pub fn new_generation(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn clear(&mut self) {
+        for slot in self.buckets.iter_mut().flatten() {
+            *slot = Slot::default();
+        }
+        *self.generation.get_mut() = 0;
+        for counter in [&mut self.probes, &mut self.hits, &mut self.stores, &mut self.collisions] {
+            *counter.get_mut() = 0;
         }
     }
 
-    pub fn put(&mut self, e: TTEntry) {
-        let idx = e.hash as usize & self.divisor;
-        match self.table[idx] {
-            Some(old) if old.hash == e.hash && old.depth > e.depth => {},
-            _ => self.table[idx] = Some(e),
+    pub fn stats(&self) -> TTStats {
+        TTStats {
+            probes: self.probes.load(Ordering::Relaxed),
+            hits: self.hits.load(Ordering::Relaxed),
+            stores: self.stores.load(Ordering::Relaxed),
+            collisions: self.collisions.load(Ordering::Relaxed),
         }
     }
+
+    /// permille of the sampled entries stored by the current search
+    pub fn usage(&self) -> usize {
+        let generation = self.generation.load(Ordering::Relaxed);
+        let sample = &self.buckets[..self.buckets.len().min(1000)];
+        let used = sample.iter()
+            .flatten()
+            .map(|s| s.data.load(Ordering::Relaxed))
+            .filter(|&data| data != 0 && generation_of(data) == generation)
+            .count();
+        used * 1000 / (sample.len() * BUCKET_SIZE)
+    }
+
+    pub fn get(&self, hash: u64) -> Option<TTEntry> {
+        self.probes.fetch_add(1, Ordering::Relaxed);
+        let entry = self.bucket(hash)
+            .iter()
+            .map(|s| (s.key.load(Ordering::Relaxed), s.data.load(Ordering::Relaxed)))
+            .find(|&(key, data)| data != 0 && key ^ data == hash)
+            .map(|(_, data)| unpack(hash, data));
+        if entry.is_some() {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        }
+        entry
+    }
+
+    /// the entry of the same position is kept if it's deeper and from this search,
+    /// otherwise an empty slot or the shallowest and oldest entry is replaced
+    pub fn put(&self, mut e: TTEntry) {
+        self.stores.fetch_add(1, Ordering::Relaxed);
+        let generation = self.generation.load(Ordering::Relaxed);
+        let age = |data: u64| generation.wrapping_sub(generation_of(data)) as i32;
+
+        let bucket = self.bucket(e.hash);
+        let slots = bucket.each_ref()
+            .map(|s| (s.key.load(Ordering::Relaxed), s.data.load(Ordering::Relaxed)));
+        let same = slots.iter().position(|&(key, data)| data != 0 && key ^ data == e.hash);
+        let i = match same {
+            Some(i) => {
+                let old = unpack(e.hash, slots[i].1);
+                if age(slots[i].1) == 0 && old.depth > e.depth {
+                    return;
+                }
+                // keep the move to search first
+                e.best = e.best.or(old.best);
+                i
+            },
+            None => {
+                let i = (0..BUCKET_SIZE)
+                    .min_by_key(|&i| match slots[i].1 {
+                        0 => i32::MIN,
+                        data => unpack(0, data).depth as i32 - AGE_WEIGHT * age(data),
+                    })
+                    .unwrap();
+                if slots[i].1 != 0 {
+                    self.collisions.fetch_add(1, Ordering::Relaxed);
+                }
+                i
+            },
+        };
+        let data = pack(&e, generation);
+        bucket[i].key.store(e.hash ^ data, Ordering::Relaxed);
+        bucket[i].data.store(data, Ordering::Relaxed);
+    }
+
+    fn bucket(&self, hash: u64) -> &Bucket {
+        &self.buckets[hash as usize & self.divisor]
+    }
 }
\ No newline at end of file
@@ -0,0 +1,237 @@
+use std::collections::HashMap;
+use std::time::Instant;
+use crate::core::board::{Board, Move, Player, Stone};
+use crate::core::pattern::Line;
+use crate::core::rule::{CheckResult, PutOutcome, Rule};
+use super::model::StopToken;
+
+// VCF (Victory by Continuous Fours) solver.
+// The attacker (side to move) makes a four with every move,
+// so the defender has to block the five point each time,
+// until the attacker makes five or a four which can't be blocked.
+
+/// attacker moves in a sequence at most
+pub const DEFAULT_DEPTH: u32 = 12;
+/// positions searched at most
+pub const DEFAULT_NODES: u64 = 100_000;
+
+#[derive(Debug)]
+pub struct Vcf<'a, R: Rule + ?Sized> {
+    pub rule: &'a R,
+    pub max_depth: u32,
+    pub budget: Budget,
+}
+
+impl<'a, R: Rule + ?Sized> Vcf<'a, R> {
+    pub fn new(rule: &'a R) -> Self {
+        Self {
+            rule,
+            max_depth: DEFAULT_DEPTH,
+            budget: Budget::nodes(DEFAULT_NODES),
+        }
+    }
+
+    /// winning line of the side to move, attacker and defender moves by turns
+    ///
+    /// the last move is a five, or a four which can't be blocked
+    /// (two five points, or a forbidden block)
+    pub fn solve(&self, board: &Board) -> Option<Vec<Move>> {
+        let defender = board.turn().next();
+        let threats = five_points(self.rule, board, empty_cells(board), defender);
+
+        let mut search = Search::new(&self.budget);
+        let mut board = *board;
+        self.attack(&mut board, self.max_depth, &threats, &mut search)
+    }
+
+    fn attack(
+        &self, board: &mut Board, depth: u32, threats: &[Move], search: &mut Search
+    ) -> Option<Vec<Move>> {
+        let hash = board.hash();
+        if !search.enter(hash, depth) {
+            return None;
+        }
+
+        let attacker = board.turn();
+        let (fives, fours) = self.candidates(board, attacker);
+        if let Some(&mv) = fives.first() {
+            return Some(vec![mv]);
+        }
+        if depth == 0 {
+            return None;
+        }
+
+        // five points of the defender not blocked yet
+        let threats: Vec<Move> = threats.iter()
+            .copied()
+            .filter(|&p| board.get(p) == Stone::None)
+            .collect();
+
+        for mv in fours {
+            // the defender makes five unless the four blocks it
+            if threats.iter().any(|&p| p != mv) {
+                continue;
+            }
+            match self.rule.put(board, mv, attacker) {
+                Ok(PutOutcome::Continue) => (),
+                Ok(_) => {
+                    board.undo_unchecked(mv);
+                    continue;
+                },
+                Err(_) => continue,
+            }
+
+            let lines = Line::all(board, mv, attacker.to_stone());
+            let points = lines.iter().flat_map(|line| line.four_points(false));
+            let blocks = five_points(self.rule, board, points, attacker);
+            let line = match blocks.as_slice() {
+                [] => None,
+                [block] => self.defend(board, *block, depth, &threats, search)
+                    .map(|line| [vec![mv], line].concat()),
+                // can't block both
+                _ => Some(vec![mv]),
+            };
+            board.undo_unchecked(mv);
+            if line.is_some() {
+                return line;
+            }
+        }
+
+        search.fail(hash, depth);
+        None
+    }
+
+    /// the defender blocks the five point, then the attacker continues
+    fn defend(
+        &self, board: &mut Board, block: Move, depth: u32, threats: &[Move],
+        search: &mut Search,
+    ) -> Option<Vec<Move>> {
+        let defender = board.turn();
+        match self.rule.put(board, block, defender) {
+            // forbidden for the defender (e.g., black under renju)
+            Err(_) => Some(Vec::new()),
+            Ok(PutOutcome::Continue) => {
+                // the block can make a four of the defender
+                let lines = Line::all(board, block, defender.to_stone());
+                let points = lines.iter().flat_map(|line| line.four_points(false));
+                let mut threats = threats.to_vec();
+                threats.extend(five_points(self.rule, board, points, defender));
+
+                let line = self.attack(board, depth - 1, &threats, search);
+                board.undo_unchecked(block);
+                line.map(|line| [vec![block], line].concat())
+            },
+            // the block wins or draws
+            Ok(_) => {
+                board.undo_unchecked(block);
+                None
+            },
+        }
+    }
+
+    /// empty cells where the attacker makes five, and where it makes a four
+    fn candidates(&self, board: &Board, attacker: Player) -> (Vec<Move>, Vec<Move>) {
+        let mut fives = Vec::new();
+        let mut fours = Vec::new();
+        for p in empty_cells(board) {
+            let lines = Line::all(board, p, attacker.to_stone());
+            if lines.iter().any(|line| line.is_five(false)) && is_five(self.rule, board, p, attacker) {
+                fives.push(p);
+            } else if lines.iter().any(|line| !line.four_points(false).is_empty()) {
+                fours.push(p);
+            }
+        }
+        (fives, fours)
+    }
+}
+
+// helper functions (shared with vct)
+
+/// positions and time a solve may spend
+#[derive(Debug, Clone)]
+pub struct Budget {
+    pub max_nodes: u64,
+    /// the solve gives up at this time, or when stopped
+    pub deadline: Option<Instant>,
+    pub stop: StopToken,
+}
+
+impl Budget {
+    /// only the number of positions is limited
+    pub fn nodes(max_nodes: u64) -> Self {
+        Self {
+            max_nodes,
+            deadline: None,
+            stop: StopToken::new(),
+        }
+    }
+
+    /// the same number of positions, also ending at the deadline or when stopped
+    pub fn until(self, deadline: Option<Instant>, stop: StopToken) -> Self {
+        Self { deadline, stop, ..self }
+    }
+}
+
+/// nodes counted against the budget, and the positions known to fail
+pub(super) struct Search<'b> {
+    budget: &'b Budget,
+    nodes: u64,
+    // hash -> depth the position is searched to without a win
+    failed: HashMap<u64, u32>,
+}
+
+impl<'b> Search<'b> {
+    pub(super) fn new(budget: &'b Budget) -> Self {
+        Self { budget, nodes: 0, failed: HashMap::new() }
+    }
+
+    /// counts the position, false if the budget is spent
+    /// or the position already failed at the depth
+    pub(super) fn enter(&mut self, hash: u64, depth: u32) -> bool {
+        self.nodes += 1;
+        let spent = self.nodes > self.budget.max_nodes
+            || self.budget.stop.is_stopped()
+            || self.budget.deadline.is_some_and(|d| Instant::now() >= d);
+        !spent && self.failed.get(&hash).is_none_or(|&d| d < depth)
+    }
+
+    pub(super) fn fail(&mut self, hash: u64, depth: u32) {
+        self.failed.insert(hash, depth);
+    }
+}
+
+pub(super) fn empty_cells(board: &Board) -> impl Iterator<Item = Move> + '_ {
+    (0..board.size())
+        .flat_map(|y| (0..board.size()).map(move |x| Move { x, y }))
+        .filter(|&p| board.get(p) == Stone::None)
+}
+
+/// points where five stones of the player would be in a line,
+/// cheaper than the rule, which `five_points` checks after this
+pub(super) fn five_shapes<'b>(
+    board: &'b Board, points: impl Iterator<Item = Move> + 'b, player: Player
+) -> impl Iterator<Item = Move> + 'b {
+    points.filter(move |&p| {
+        Line::all(board, p, player.to_stone())
+            .iter()
+            .any(|line| line.is_five(false))
+    })
+}
+
+/// points where the player wins by the rule
+pub(super) fn five_points<R: Rule + ?Sized>(
+    rule: &R, board: &Board, points: impl Iterator<Item = Move>, player: Player
+) -> Vec<Move> {
+    let mut v: Vec<Move> = Vec::new();
+    for p in points {
+        if !v.contains(&p) && is_five(rule, board, p, player) {
+            v.push(p);
+        }
+    }
+    v
+}
+
+pub(super) fn is_five<R: Rule + ?Sized>(rule: &R, board: &Board, p: Move, player: Player) -> bool {
+    board.get(p) == Stone::None
+        && matches!(rule.check(board, p, player), CheckResult::Win)
+}
\ No newline at end of file
@@ -0,0 +1,267 @@
+use crate::core::board::{Board, Move, Player, Stone};
+use crate::core::pattern::{Line, Pattern, DIRECTION};
+use crate::core::rule::{PutOutcome, Rule};
+use crate::core::board::MAX_SIZE;
+use super::vcf::{five_points, five_shapes, Budget, Search};
+
+// VCT (Victory by Continuous Threats) solver.
+// The attacker (side to move) makes a four or an open three with every move.
+// The defender blocks the threat, or makes a four of its own (counter-four)
+// which the attacker has to block first. The attacker wins if every defence loses.
+
+/// threats of the attacker in a sequence at most,
+/// fewer than vcf since threes widen the tree
+pub const DEFAULT_DEPTH: u32 = 6;
+/// budget of `Vct::new`
+pub const DEFAULT_NODES: u64 = 20_000;
+
+#[derive(Debug)]
+pub struct Vct<'a, R: Rule + ?Sized> {
+    pub rule: &'a R,
+    pub max_depth: u32,
+    pub budget: Budget,
+}
+
+impl<'a, R: Rule + ?Sized> Vct<'a, R> {
+    pub fn new(rule: &'a R) -> Self {
+        Self {
+            rule,
+            max_depth: DEFAULT_DEPTH,
+            budget: Budget::nodes(DEFAULT_NODES),
+        }
+    }
+
+    /// winning line of the side to move, attacker and defender moves by turns
+    ///
+    /// the attacker takes the fewest threats (deepening one by one),
+    /// the defender plays the defence lasting longest,
+    /// the line ends with five, or with threats which can't be defended
+    pub fn solve(&self, board: &Board) -> Option<Vec<Move>> {
+        let mut search = Search::new(&self.budget);
+        let mut board = *board;
+        (0..=self.max_depth).find_map(|depth| self.attack(&mut board, depth, &mut search))
+    }
+
+    /// the attacker to move, `depth` threats are left
+    fn attack(&self, board: &mut Board, depth: u32, search: &mut Search) -> Option<Vec<Move>> {
+        let hash = board.hash();
+        if !search.enter(hash, depth) {
+            return None;
+        }
+
+        let attacker = board.turn();
+        if let Some(&five) = self.fives(board, attacker).first() {
+            return Some(vec![five]);
+        }
+
+        // a four of the defender is blocked first,
+        // then the defender has to answer the threats left on the board
+        let line = match self.fives(board, attacker.next()).as_slice() {
+            [] => self.threaten(board, depth, search),
+            &[block] => {
+                match self.rule.put(board, block, attacker) {
+                    Ok(PutOutcome::Continue) => {
+                        let line = self.defend(board, depth, search);
+                        board.undo_unchecked(block);
+                        line.map(|line| [vec![block], line].concat())
+                    },
+                    Ok(_) => {
+                        board.undo_unchecked(block);
+                        None
+                    },
+                    Err(_) => None,
+                }
+            },
+            _ => None,
+        };
+
+        if line.is_none() {
+            search.fail(hash, depth);
+        }
+        line
+    }
+
+    /// tries every threat of the attacker
+    fn threaten(&self, board: &mut Board, depth: u32, search: &mut Search) -> Option<Vec<Move>> {
+        if depth == 0 {
+            return None;
+        }
+        let attacker = board.turn();
+        for mv in self.threats(board, attacker) {
+            match self.rule.put(board, mv, attacker) {
+                Ok(PutOutcome::Continue) => (),
+                Ok(_) => {
+                    board.undo_unchecked(mv);
+                    continue;
+                },
+                Err(_) => continue,
+            }
+            let line = self.defend(board, depth - 1, search);
+            board.undo_unchecked(mv);
+            if let Some(line) = line {
+                return Some([vec![mv], line].concat());
+            }
+        }
+        None
+    }
+
+    /// the defender to move against the threats on the board,
+    /// the line of the longest defence if every defence loses
+    fn defend(&self, board: &mut Board, depth: u32, search: &mut Search) -> Option<Vec<Move>> {
+        let defender = board.turn();
+        let attacker = defender.next();
+        if !self.fives(board, defender).is_empty() {
+            return None;
+        }
+
+        let defences = match self.fives(board, attacker).as_slice() {
+            [] => {
+                let points = self.straight_four_points(board, attacker);
+                if points.is_empty() {
+                    // no threat left
+                    return None;
+                }
+                let mut defences = self.blocks(board, &points, defender);
+                for p in self.fours(board, defender) {
+                    if !defences.contains(&p) {
+                        defences.push(p);
+                    }
+                }
+                defences
+            },
+            // a counter-four is answered with five
+            &[block] => vec![block],
+            _ => return Some(Vec::new()),
+        };
+
+        let mut longest = Vec::new();
+        for mv in defences {
+            match self.rule.put(board, mv, defender) {
+                Ok(PutOutcome::Continue) => (),
+                Ok(_) => {
+                    board.undo_unchecked(mv);
+                    return None;
+                },
+                // forbidden defence (e.g., black under renju)
+                Err(_) => continue,
+            }
+            let line = self.attack(board, depth, search);
+            board.undo_unchecked(mv);
+            match line {
+                Some(line) if line.len() + 1 > longest.len() => {
+                    longest = [vec![mv], line].concat();
+                },
+                Some(_) => (),
+                None => return None,
+            }
+        }
+        Some(longest)
+    }
+
+    /// cells of the defender which leave no straight four point
+    fn blocks(&self, board: &Board, points: &[Move], defender: Player) -> Vec<Move> {
+        let attacker = defender.next();
+        let mut cells = Vec::new();
+        for &p in points {
+            cells.push(p);
+            for direction in DIRECTION {
+                let (dx, dy) = direction.delta();
+                for i in (-4..=4).filter(|&i| i != 0) {
+                    if let Some(c) = p.shift(dx * i, dy * i, board.size())
+                        && board.get(c) == Stone::None
+                        && !cells.contains(&c)
+                    {
+                        cells.push(c);
+                    }
+                }
+            }
+        }
+
+        // a stone of the defender can't make new points, so only the points are checked
+        cells.retain(|&c| {
+            let mut next = *board;
+            next.put(c, defender.to_stone());
+            points.iter()
+                .filter(|&&p| p != c)
+                .all(|&p| !self.is_straight_four(&next, p, attacker))
+        });
+        cells
+    }
+
+    /// fours, and then open threes of the player
+    fn threats(&self, board: &Board, player: Player) -> Vec<Move> {
+        let mut fours = Vec::new();
+        let mut threes = Vec::new();
+        for p in near_cells(board, player) {
+            let lines = Line::all(board, p, player.to_stone());
+            let lines = lines.iter().filter(|line| line.nearby() >= 3);
+            if lines.clone().any(|line| !line.four_points(false).is_empty()) {
+                fours.push(p);
+            } else if lines.clone().any(|line| line.classify(false) == Pattern::OpenThree) {
+                threes.push(p);
+            }
+        }
+        fours.extend(threes);
+        fours
+    }
+
+    /// empty cells where the player makes a four
+    fn fours(&self, board: &Board, player: Player) -> Vec<Move> {
+        near_cells(board, player)
+            .into_iter()
+            .filter(|&p| {
+                Line::all(board, p, player.to_stone())
+                    .iter()
+                    .any(|line| line.nearby() >= 4 && !line.four_points(false).is_empty())
+            })
+            .collect()
+    }
+
+    /// empty cells where the player makes a straight four by a valid move
+    fn straight_four_points(&self, board: &Board, player: Player) -> Vec<Move> {
+        near_cells(board, player)
+            .into_iter()
+            .filter(|&p| self.is_straight_four(board, p, player))
+            .collect()
+    }
+
+    fn is_straight_four(&self, board: &Board, p: Move, player: Player) -> bool {
+        board.get(p) == Stone::None
+            && Line::all(board, p, player.to_stone())
+                .iter()
+                .any(|line| line.nearby() >= 4 && line.classify(false) == Pattern::OpenFour)
+            && self.rule.is_valid(board, p, player)
+    }
+
+    /// empty cells where the player wins
+    fn fives(&self, board: &Board, player: Player) -> Vec<Move> {
+        let cells = near_cells(board, player);
+        five_points(self.rule, board, five_shapes(board, cells.into_iter(), player), player)
+    }
+}
+
+/// empty cells within two cells of the stones of the player on a line,
+/// threes, fours and fives can't be made elsewhere
+fn near_cells(board: &Board, player: Player) -> Vec<Move> {
+    let stone = player.to_stone();
+    let mut seen = [[false; MAX_SIZE]; MAX_SIZE];
+    let mut cells = Vec::new();
+    for s in (0..board.size()).flat_map(|y| (0..board.size()).map(move |x| Move { x, y })) {
+        if board.get(s) != stone {
+            continue;
+        }
+        for direction in DIRECTION {
+            let (dx, dy) = direction.delta();
+            for i in [-2, -1, 1, 2] {
+                if let Some(c) = s.shift(dx * i, dy * i, board.size())
+                    && board.get(c) == Stone::None
+                    && !seen[c.y][c.x]
+                {
+                    seen[c.y][c.x] = true;
+                    cells.push(c);
+                }
+            }
+        }
+    }
+    cells
+}
\ No newline at end of file
@@ -1,4 +1,7 @@
 
+use super::pattern::{Direction, DIRECTION};
+use super::zobrist;
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum Stone {
     None, Black, White,
@@ -11,18 +14,56 @@ pub enum Turn {
 
 pub type Player = Turn;
 
+/// largest supported board dimension
+pub const MAX_SIZE: usize = 19;
+/// board dimension used by `Board::blank`
+pub const DEFAULT_SIZE: usize = 15;
+
+// number of lines in a direction (diagonals are the most)
+const LINES: usize = 2 * MAX_SIZE - 1;
+
+/// size x size board, cells out of the size are never used
+///
+/// stones are kept as bitsets of every line in every direction,
+/// so a line through a cell is read as a word
 #[derive(PartialEq, Debug, Clone, Copy)]
 pub struct Board {
-    v: [[Stone; 15]; 15],
+    /// [color][direction][line], see `line_index`
+    lines: [[[u32; LINES]; 4]; 2],
+    size: usize,
     turn: Turn,
     ply: u32,
+    /// zobrist key of the stones and the side to move
+    hash: u64,
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Move {
     pub x: usize, pub y: usize
 }
 
+/// stones on a line as bitsets, bit i is the i-th cell of the line
+/// (cells are ordered by x, or by y on vertical lines)
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct LineBits {
+    pub black: u32,
+    pub white: u32,
+    /// cells on the board
+    pub mask: u32,
+    /// bit of the cell the line was taken through
+    pub pos: usize,
+}
+
+impl LineBits {
+    pub fn stones(&self, stone: Stone) -> u32 {
+        match stone {
+            Stone::Black => self.black,
+            Stone::White => self.white,
+            Stone::None => self.mask & !(self.black | self.white),
+        }
+    }
+}
+
 impl Turn {
     pub fn next(&self) -> Self {
         match self {
@@ -41,28 +82,115 @@ impl Turn {
 
 impl Board {
     pub fn blank() -> Self {
+        Self::new(DEFAULT_SIZE)
+    }
+
+    /// blank board of the given dimension (5 to MAX_SIZE)
+    pub fn new(size: usize) -> Self {
+        assert!((5..=MAX_SIZE).contains(&size), "unsupported board size {size}");
         Self {
-            v: [[Stone::None; 15]; 15],
+            lines: [[[0; LINES]; 4]; 2],
+            size,
             turn: Turn::Black,
             ply: 0,
+            hash: zobrist::blank(size),
         }
     }
 
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn center(&self) -> Move {
+        Move { x: self.size / 2, y: self.size / 2 }
+    }
+
     pub fn get(&self, mv: Move) -> Stone {
-        self.v[mv.y][mv.x]
+        let bit = 1 << mv.x;
+        if self.lines[0][0][mv.y] & bit != 0 {
+            Stone::Black
+        } else if self.lines[1][0][mv.y] & bit != 0 {
+            Stone::White
+        } else {
+            Stone::None
+        }
     }
 
     pub(super) fn put_unchecked(&mut self, mv: Move, stone: Stone) {
         self.turn = self.turn.next();
         self.ply += 1;
-        self.v[mv.y][mv.x] = stone;
+        self.hash ^= zobrist::side();
+        if let Some(color) = Self::color_index(stone) {
+            self.flip(mv, color);
+        }
     }
 
     pub fn undo_unchecked(&mut self, mv: Move) {
         // next is previous because there's only black or white turn
         self.turn = self.turn.next();
         self.ply -= 1;
-        self.v[mv.y][mv.x] = Stone::None;
+        self.hash ^= zobrist::side();
+        match self.get(mv) {
+            Stone::Black => self.flip(mv, 0),
+            Stone::White => self.flip(mv, 1),
+            Stone::None => (),
+        }
+    }
+
+    /// line through mv in the direction
+    pub fn line_bits(&self, mv: Move, direction: Direction) -> LineBits {
+        let (line, pos) = Self::line_index(mv, direction);
+        let d = direction as usize;
+        LineBits {
+            black: self.lines[0][d][line],
+            white: self.lines[1][d][line],
+            mask: self.line_mask(mv, direction),
+            pos,
+        }
+    }
+
+    fn flip(&mut self, mv: Move, color: usize) {
+        for direction in DIRECTION {
+            let (line, pos) = Self::line_index(mv, direction);
+            self.lines[color][direction as usize][line] ^= 1 << pos;
+        }
+        self.hash ^= zobrist::stone(mv, color);
+    }
+
+    fn color_index(stone: Stone) -> Option<usize> {
+        match stone {
+            Stone::None => None,
+            Stone::Black => Some(0),
+            Stone::White => Some(1),
+        }
+    }
+
+    /// (line, bit) of the cell
+    fn line_index(mv: Move, direction: Direction) -> (usize, usize) {
+        match direction {
+            Direction::Horizontal => (mv.y, mv.x),
+            Direction::Vertical => (mv.x, mv.y),
+            Direction::DiagDown => (mv.x + MAX_SIZE - 1 - mv.y, mv.x),
+            Direction::DiagUp => (mv.x + mv.y, mv.x),
+        }
+    }
+
+    /// bits of the line through mv which are on the board
+    fn line_mask(&self, mv: Move, direction: Direction) -> u32 {
+        let last = self.size - 1;
+        // range of the bit (x, or y on vertical lines)
+        let (lo, hi) = match direction {
+            Direction::Horizontal | Direction::Vertical => (0, last),
+            Direction::DiagDown => {
+                let (x, y) = (mv.x, mv.y);
+                (x.saturating_sub(y), (last + x - y).min(last))
+            },
+            Direction::DiagUp => {
+                let sum = mv.x + mv.y;
+                (sum.saturating_sub(last), sum.min(last))
+            },
+        };
+        ((1u64 << (hi + 1)) - (1u64 << lo)) as u32
     }
 
     pub fn put(&mut self, mv: Move, stone: Stone) -> bool {
@@ -77,23 +205,41 @@ impl Board {
         self.turn
     }
 
+    /// the side to move changes without a stone,
+    /// to see the threats of the opponent (not a move of the game)
+    pub fn pass(&mut self) {
+        self.turn = self.turn.next();
+        self.hash ^= zobrist::side();
+    }
+
     pub fn ply(&self) -> u32 {
         self.ply
     }
+
+    /// zobrist key of the position, the same in every run
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    /// number of empty cells
+    pub fn empty_count(&self) -> usize {
+        self.size * self.size - self.ply as usize
+    }
 }
 
 impl Move {
-    pub fn new(x: usize, y: usize) -> Option<Self> {
-        if x >= 15 || y >= 15 {
+    /// None if out of the size x size board
+    pub fn new(x: usize, y: usize, size: usize) -> Option<Self> {
+        if x >= size || y >= size {
             return None;
         }
         Some(Self { x, y })
     }
 
-    pub fn shift(&self, dx: i32, dy: i32) -> Option<Self> {
+    pub fn shift(&self, dx: i32, dy: i32, size: usize) -> Option<Self> {
         let x = self.x as i32 - dx;
         let y = self.y as i32 - dy;
-        if x < 0 || y < 0 || x >= 15 || y >= 15 {
+        if x < 0 || y < 0 || x >= size as i32 || y >= size as i32 {
             return None;
         }
         Some(
@@ -0,0 +1,97 @@
+use super::board::{Board, Move};
+use super::rule::{Rule, PutOutcome, PutError};
+
+// Game record on top of Board.
+// Board is copied a lot during search, so the history lives here
+// and the board only keeps the current position.
+
+#[derive(Debug, Clone)]
+pub struct Game {
+    board: Board,
+    /// every move put so far with its outcome, including undone ones
+    moves: Vec<(Move, PutOutcome)>,
+    /// number of moves currently on the board
+    cursor: usize,
+}
+
+impl Game {
+    pub fn new(size: usize) -> Self {
+        Self {
+            board: Board::new(size),
+            moves: Vec::new(),
+            cursor: 0,
+        }
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    /// moves on the board, in order
+    pub fn history(&self) -> &[(Move, PutOutcome)] {
+        &self.moves[..self.cursor]
+    }
+
+    pub fn last_move(&self) -> Option<Move> {
+        self.history().last().map(|&(mv, _)| mv)
+    }
+
+    /// outcome of the last move, Continue at the start
+    pub fn outcome(&self) -> PutOutcome {
+        self.history().last().map_or(PutOutcome::Continue, |&(_, outcome)| outcome)
+    }
+
+    /// puts a stone of the side to move, undone moves are discarded
+    pub fn put<R: Rule + ?Sized>(
+        &mut self, rule: &R, mv: Move
+    ) -> Result<PutOutcome, PutError> {
+        let turn = self.board.turn();
+        let outcome = rule.put(&mut self.board, mv, turn)?;
+        self.moves.truncate(self.cursor);
+        self.moves.push((mv, outcome));
+        self.cursor += 1;
+        Ok(outcome)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.cursor > 0
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.cursor < self.moves.len()
+    }
+
+    /// takes back the last move
+    pub fn undo(&mut self) -> Option<Move> {
+        if !self.can_undo() {
+            return None;
+        }
+        self.cursor -= 1;
+        let (mv, _) = self.moves[self.cursor];
+        self.board.undo_unchecked(mv);
+        Some(mv)
+    }
+
+    /// puts the last undone move again
+    pub fn redo(&mut self) -> Option<Move> {
+        if !self.can_redo() {
+            return None;
+        }
+        let (mv, _) = self.moves[self.cursor];
+        let stone = self.board.turn().to_stone();
+        self.board.put_unchecked(mv, stone);
+        self.cursor += 1;
+        Some(mv)
+    }
+
+    /// forgets the undone moves
+    pub fn clear_redo(&mut self) {
+        self.moves.truncate(self.cursor);
+    }
+
+    /// walks back and forth to the position after `ply` moves
+    pub fn goto(&mut self, ply: usize) {
+        while self.cursor > ply && self.undo().is_some() { }
+        while self.cursor < ply && self.redo().is_some() { }
+    }
+}
\ No newline at end of file
@@ -1,2 +1,6 @@
 pub mod board;
-pub mod rule;
\ No newline at end of file
+pub mod game;
+pub mod opening;
+pub mod pattern;
+pub mod rule;
+pub mod zobrist;
\ No newline at end of file
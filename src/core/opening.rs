@@ -0,0 +1,196 @@
+use super::board::{Board, Move, Player};
+use super::rule::{Rule, Forbidden};
+
+// Opening rules for balanced openings.
+// Pro / Long Pro restrict where black puts stones (ProRule wraps a rule),
+// Swap / Swap2 decide who plays which color (Opening state machine).
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum OpeningRule {
+    Free, Pro, LongPro, Swap, Swap2,
+}
+
+impl OpeningRule {
+    pub const ALL: [OpeningRule; 5] = [
+        OpeningRule::Free, OpeningRule::Pro, OpeningRule::LongPro,
+        OpeningRule::Swap, OpeningRule::Swap2,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            OpeningRule::Free => "Free",
+            OpeningRule::Pro => "Pro",
+            OpeningRule::LongPro => "Long Pro",
+            OpeningRule::Swap => "Swap",
+            OpeningRule::Swap2 => "Swap2",
+        }
+    }
+}
+
+/// (Pro / Long Pro)
+/// first black stone on the center,
+/// second black stone outside the 5x5 (pro) or 7x7 (long pro) zone
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct ProRule<R: Rule> {
+    pub rule: R,
+    /// minimum distance of the second black stone from the center
+    pub distance: usize,
+}
+
+impl<R: Rule> ProRule<R> {
+    pub fn pro(rule: R) -> Self {
+        Self { rule, distance: 3 }
+    }
+
+    pub fn long_pro(rule: R) -> Self {
+        Self { rule, distance: 4 }
+    }
+
+    fn is_placeable(&self, board: &Board, mv: Move) -> bool {
+        let center = board.center();
+        let dist = mv.x.abs_diff(center.x).max(mv.y.abs_diff(center.y));
+        match board.ply() {
+            0 => dist == 0,
+            2 => dist >= self.distance,
+            _ => true,
+        }
+    }
+}
+
+impl<R: Rule> Rule for ProRule<R> {
+    fn is_valid(&self, board: &Board, mv: Move, player: Player) -> bool {
+        self.is_placeable(board, mv) && self.rule.is_valid(board, mv, player)
+    }
+
+    fn forbidden(&self, board: &Board, mv: Move, player: Player) -> Option<Forbidden> {
+        if !self.is_placeable(board, mv) {
+            return Some(Forbidden::other());
+        }
+        self.rule.forbidden(board, mv, player)
+    }
+
+    fn is_winning(&self, board: &Board, mv: Move, player: Player) -> bool {
+        self.rule.is_winning(board, mv, player)
+    }
+
+    fn is_draw(&self, board: &Board, mv: Move, player: Player) -> bool {
+        self.rule.is_draw(board, mv, player)
+    }
+}
+
+/// players of the game, not colors
+/// (first player starts as black)
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Side {
+    First, Second,
+}
+
+/// decision made by a player during swap openings
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Choice {
+    /// play black
+    Black,
+    /// play white
+    White,
+    /// (swap2) put two more stones and let the opponent choose color
+    PutTwo,
+}
+
+/// what the game waits for
+#[derive(Debug, Clone, PartialEq)]
+pub enum Request {
+    /// the side puts the next stone, color follows the board turn
+    Put(Side),
+    /// the side chooses one of the options
+    Choose(Side, Vec<Choice>),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Phase {
+    /// first player puts three stones, then second player chooses
+    Initial,
+    /// (swap2) second player puts two stones, then first player chooses
+    PutTwo,
+    /// colors are decided
+    Normal,
+}
+
+/// state of the swap protocol in a game
+#[derive(Debug, Copy, Clone)]
+pub struct Opening {
+    rule: OpeningRule,
+    phase: Phase,
+    /// color of the first player
+    first: Player,
+}
+
+impl Opening {
+    pub fn new(rule: OpeningRule) -> Self {
+        let phase = match rule {
+            OpeningRule::Swap | OpeningRule::Swap2 => Phase::Initial,
+            _ => Phase::Normal,
+        };
+        Self {
+            rule,
+            phase,
+            first: Player::Black,
+        }
+    }
+
+    pub fn rule(&self) -> OpeningRule {
+        self.rule
+    }
+
+    pub fn color(&self, side: Side) -> Player {
+        match side {
+            Side::First => self.first,
+            Side::Second => self.first.next(),
+        }
+    }
+
+    pub fn side(&self, color: Player) -> Side {
+        if color == self.first { Side::First } else { Side::Second }
+    }
+
+    pub fn request(&self, board: &Board) -> Request {
+        match self.phase {
+            Phase::Initial if board.ply() < 3 => Request::Put(Side::First),
+            Phase::Initial => {
+                let mut options = vec![Choice::Black, Choice::White];
+                if self.rule == OpeningRule::Swap2 {
+                    options.push(Choice::PutTwo);
+                }
+                Request::Choose(Side::Second, options)
+            },
+            Phase::PutTwo if board.ply() < 5 => Request::Put(Side::Second),
+            Phase::PutTwo => {
+                Request::Choose(Side::First, vec![Choice::Black, Choice::White])
+            },
+            Phase::Normal => Request::Put(self.side(board.turn())),
+        }
+    }
+
+    /// applies the choice of the side, returns false if it's not expected
+    pub fn choose(&mut self, board: &Board, side: Side, choice: Choice) -> bool {
+        match self.request(board) {
+            Request::Choose(s, options) if s == side && options.contains(&choice) => (),
+            _ => return false,
+        }
+
+        match choice {
+            Choice::Black | Choice::White => {
+                let color = match choice {
+                    Choice::Black => Player::Black,
+                    _ => Player::White,
+                };
+                self.first = match side {
+                    Side::First => color,
+                    Side::Second => color.next(),
+                };
+                self.phase = Phase::Normal;
+            },
+            Choice::PutTwo => self.phase = Phase::PutTwo,
+        }
+        true
+    }
+}
\ No newline at end of file
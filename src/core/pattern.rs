@@ -0,0 +1,324 @@
+use super::board::{Board, Stone, Move};
+
+// Pattern classifier for a single line passing through a stone.
+// Rules, evaluation and bots can share this to detect threes and fours,
+// including broken ones like X X _ X or X _ X X X.
+
+pub const DIRECTION: [Direction; 4] = [
+    Direction::Horizontal, Direction::Vertical,
+    Direction::DiagDown, Direction::DiagUp,
+];
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Direction {
+    Horizontal, Vertical, DiagDown, DiagUp,
+}
+
+impl Direction {
+    pub fn delta(&self) -> (i32, i32) {
+        match self {
+            Direction::Horizontal => (1, 0),
+            Direction::Vertical => (0, 1),
+            Direction::DiagDown => (1, 1),
+            Direction::DiagUp => (1, -1),
+        }
+    }
+}
+
+/// shape of a line made by the put stone
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Pattern {
+    None,
+    /// one more stone makes a four
+    Three,
+    /// one more stone makes a straight four
+    OpenThree,
+    /// one more stone makes five
+    Four,
+    /// two fours in a single line (X _ X X X _ X)
+    DoubleFour,
+    /// straight four (_ X X X X _)
+    OpenFour,
+    Five,
+    /// six or more, only when overline is not five
+    Overline,
+}
+
+impl Pattern {
+    /// number of fours for 4-4 detection
+    pub fn fours(&self) -> usize {
+        match self {
+            Pattern::Four | Pattern::OpenFour => 1,
+            Pattern::DoubleFour => 2,
+            _ => 0,
+        }
+    }
+}
+
+/// open ends of contiguous stones
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum OpenType {
+    Open, HalfOpen, Closed
+}
+
+// cells within distance REACH from the put stone are copied
+const REACH: i32 = 5;
+const CENTER: usize = REACH as usize;
+const LINE_LEN: usize = 2 * CENTER + 1;
+
+/// cells of the line as bitsets, bit i is the i-th cell
+/// (cells out of the board are in none of them)
+#[derive(Debug, Copy, Clone)]
+pub struct Line {
+    own: u16,
+    empty: u16,
+    opponent: u16,
+    mv: Move,
+    direction: Direction,
+    /// dimension of the board
+    size: usize,
+}
+
+impl Line {
+    /// the center cell is treated as stone is already put
+    pub fn new(board: &Board, mv: Move, stone: Stone, direction: Direction) -> Self {
+        let bits = board.line_bits(mv, direction);
+        let opponent = match stone {
+            Stone::White => Stone::Black,
+            _ => Stone::White,
+        };
+        // move the cell of mv to CENTER
+        let window = |set: u32| {
+            let set = (set as u64) << CENTER >> bits.pos;
+            (set & ((1 << LINE_LEN) - 1)) as u16
+        };
+        let center = 1 << CENTER;
+        Self {
+            own: window(bits.stones(stone)) | center,
+            empty: window(bits.stones(Stone::None)) & !center,
+            opponent: window(bits.stones(opponent)) & !center,
+            mv,
+            direction,
+            size: board.size(),
+        }
+    }
+
+    /// lines of every direction
+    pub fn all(board: &Board, mv: Move, stone: Stone) -> [Self; 4] {
+        DIRECTION.map(|d| Self::new(board, mv, stone, d))
+    }
+
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    /// `exact`: only exactly five stones make five (jangmok is not five)
+    pub fn classify(&self, exact: bool) -> Pattern {
+        let run = self.run();
+        if run == 5 || (run > 5 && !exact) {
+            return Pattern::Five;
+        }
+        if run > 5 {
+            return Pattern::Overline;
+        }
+
+        let fours = self.four_cells(exact);
+        if self.is_straight_four(exact) {
+            return Pattern::OpenFour;
+        }
+        match fours.len() {
+            0 => (),
+            1 => return Pattern::Four,
+            _ => return Pattern::DoubleFour,
+        }
+
+        if !self.three_cells(exact).is_empty() {
+            return Pattern::OpenThree;
+        }
+
+        let three = (CENTER - 4..=CENTER + 4)
+            .filter(|&i| self.is_empty(i))
+            .any(|i| !self.with(i).four_cells(exact).is_empty());
+        if three {
+            Pattern::Three
+        } else {
+            Pattern::None
+        }
+    }
+
+    /// number of contiguous stones including the put stone
+    pub fn run(&self) -> usize {
+        let (start, end) = self.run_range();
+        end - start + 1
+    }
+
+    /// number of stones within four cells, including the put stone
+    /// (a three needs 3 of them, a four needs 4)
+    pub fn nearby(&self) -> u32 {
+        let window = ((1 << 9) - 1) << (CENTER - 4);
+        (self.own & window).count_ones()
+    }
+
+    pub fn is_five(&self, exact: bool) -> bool {
+        let run = self.run();
+        if exact { run == 5 } else { run >= 5 }
+    }
+
+    /// whether contiguous stones are blocked at the ends
+    /// (a run reaching out of the copied cells is closed on that side)
+    pub fn open_type(&self) -> OpenType {
+        self.open_type_with(|_, i| i.is_some_and(|i| self.is_empty(i)))
+    }
+
+    /// same as `open_type`, but only opponent stones block
+    /// (the edge of the board is open, a run reaching out of the copied cells
+    /// is followed on the board)
+    pub fn open_type_by_stone(&self, board: &Board) -> OpenType {
+        self.open_type_with(|end, i| match i {
+            Some(i) => self.opponent & (1 << i) == 0,
+            None => self.is_open_on_board(board, end),
+        })
+    }
+
+    /// stones forming the pattern, including the put stone
+    pub fn stones(&self, exact: bool) -> Vec<Move> {
+        // fives (or straight fours) made from the pattern span its stones
+        let fives: Vec<(usize, usize)> = match self.classify(exact) {
+            Pattern::Four | Pattern::OpenFour | Pattern::DoubleFour => {
+                self.four_cells(exact)
+                    .into_iter()
+                    .map(|i| self.with(i).run_range())
+                    .collect()
+            },
+            Pattern::OpenThree => {
+                self.three_cells(exact)
+                    .into_iter()
+                    .map(|i| self.with(i).run_range())
+                    .collect()
+            },
+            Pattern::Three => {
+                let mut v = Vec::new();
+                for i in (CENTER - 4..=CENTER + 4).filter(|&i| self.is_empty(i)) {
+                    let line = self.with(i);
+                    for j in line.four_cells(exact) {
+                        v.push(line.with(j).run_range());
+                    }
+                }
+                v
+            },
+            _ => Vec::new(),
+        };
+
+        let (start, end) = fives.into_iter()
+            .fold(self.run_range(), |(s1, e1), (s2, e2)| (s1.min(s2), e1.max(e2)));
+        let cells: Vec<usize> = (start..=end)
+            .filter(|&i| self.own & (1 << i) != 0)
+            .collect();
+        self.points(cells)
+    }
+
+    /// empty points which make five with the put stone
+    pub fn four_points(&self, exact: bool) -> Vec<Move> {
+        self.points(self.four_cells(exact))
+    }
+
+    /// empty points which make straight four with the put stone
+    pub fn three_points(&self, exact: bool) -> Vec<Move> {
+        self.points(self.three_cells(exact))
+    }
+
+    // helper functions (work on cell index)
+
+    /// board position of i-th cell
+    fn point(&self, i: usize) -> Option<Move> {
+        let (dx, dy) = self.direction.delta();
+        let offset = i as i32 - REACH;
+        self.mv.shift(-dx * offset, -dy * offset, self.size)
+    }
+
+    fn points(&self, cells: Vec<usize>) -> Vec<Move> {
+        cells.into_iter()
+            .filter_map(|i| self.point(i))
+            .collect()
+    }
+
+    fn is_empty(&self, i: usize) -> bool {
+        self.empty & (1 << i) != 0
+    }
+
+    /// `is_open(end, i)`: whether the cell `i` next to the `end` of the run is open,
+    /// `i` is None if the run reaches out of the copied cells
+    fn open_type_with(&self, is_open: impl Fn(usize, Option<usize>) -> bool) -> OpenType {
+        let (start, end) = self.run_range();
+        let open1 = is_open(start, start.checked_sub(1));
+        let open2 = is_open(end, Some(end + 1).filter(|&i| i < LINE_LEN));
+
+        if open1 && open2 { OpenType::Open }
+        else if open1 ^ open2 { OpenType::HalfOpen }
+        else { OpenType::Closed }
+    }
+
+    /// the run reaches the last copied cell `end`,
+    /// whether no opponent stone is next to it on the board
+    fn is_open_on_board(&self, board: &Board, end: usize) -> bool {
+        let (dx, dy) = self.direction.delta();
+        let step = if end == 0 { -1 } else { 1 };
+        let Some(last) = self.point(end) else {
+            return true;
+        };
+        let stone = board.get(last);
+        let mut offset = end as i32 - REACH;
+        loop {
+            offset += step;
+            match self.mv.shift(-dx * offset, -dy * offset, self.size) {
+                Some(p) if board.get(p) == stone => (),
+                Some(p) => return board.get(p) == Stone::None,
+                // the edge of the board
+                None => return true,
+            }
+        }
+    }
+
+    fn with(&self, i: usize) -> Self {
+        let mut line = *self;
+        line.own |= 1 << i;
+        line.empty &= !(1 << i);
+        line
+    }
+
+    /// range of contiguous stones including the center
+    fn run_range(&self) -> (usize, usize) {
+        let own = self.own as u32;
+        // contiguous bits from the center to each side (the center is own)
+        let below = (own << (31 - CENTER)).leading_ones() as usize;
+        let above = (own >> CENTER).trailing_ones() as usize;
+        (CENTER + 1 - below, CENTER + above - 1)
+    }
+
+    fn four_cells(&self, exact: bool) -> Vec<usize> {
+        (CENTER - 4..=CENTER + 4)
+            .filter(|&i| self.is_empty(i))
+            .filter(|&i| self.with(i).is_five(exact))
+            .collect()
+    }
+
+    fn is_straight_four(&self, exact: bool) -> bool {
+        let (start, end) = self.run_range();
+        if end - start + 1 != 4 {
+            return false;
+        }
+        let cells = self.four_cells(exact);
+        cells.contains(&(start - 1)) && cells.contains(&(end + 1))
+    }
+
+    fn three_cells(&self, exact: bool) -> Vec<usize> {
+        (CENTER - 3..=CENTER + 3)
+            .filter(|&i| self.is_empty(i))
+            .filter(|&i| {
+                let line = self.with(i);
+                let (start, end) = line.run_range();
+                start <= i && i <= end && line.is_straight_four(exact)
+            })
+            .collect()
+    }
+}
\ No newline at end of file
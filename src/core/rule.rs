@@ -1,10 +1,39 @@
 use std::fmt::Debug;
 use super::board::{Board, Stone, Move, Player};
+use super::pattern::{Line, Pattern, OpenType};
 
 pub trait Rule: Debug + Send + Sync {
     fn is_valid(&self, board: &Board, mv: Move, player: Player) -> bool;
 
     fn is_winning(&self, board: &Board, mv: Move, player: Player) -> bool;
+
+    /// reason why the move is not valid, None if valid
+    /// rules with forbidden patterns should override this
+    fn forbidden(&self, board: &Board, mv: Move, player: Player) -> Option<Forbidden> {
+        if self.is_valid(board, mv, player) {
+            None
+        } else {
+            Some(Forbidden::other())
+        }
+    }
+
+    /// whether the game is drawn after the move (not winning)
+    /// default: the move fills the board
+    fn is_draw(&self, board: &Board, _mv: Move, _player: Player) -> bool {
+        board.empty_count() <= 1
+    }
+
+    fn has_legal_move(&self, board: &Board, player: Player) -> bool {
+        for y in 0..board.size() {
+            for x in 0..board.size() {
+                let mv = Move { x, y };
+                if board.get(mv) == Stone::None && self.is_valid(board, mv, player) {
+                    return true;
+                }
+            }
+        }
+        false
+    }
     
     fn check(&self, board: &Board, mv: Move, player: Player) -> CheckResult {
         let valid: bool = self.is_valid(board, mv, player);
@@ -17,7 +46,10 @@ pub trait Rule: Debug + Send + Sync {
             return CheckResult::Win;
         }
 
-        // todo: check draw
+        let draw = self.is_draw(board, mv, player);
+        if draw {
+            return CheckResult::Draw;
+        }
 
         CheckResult::LooksGood
     }
@@ -31,7 +63,11 @@ pub trait Rule: Debug + Send + Sync {
 
         let check = self.check(board, mv, player);
         match check {
-            CheckResult::Invalid => Err(PutError::Invalid),
+            CheckResult::Invalid => {
+                let reason = self.forbidden(board, mv, player)
+                    .unwrap_or_else(Forbidden::other);
+                Err(PutError::Invalid(reason))
+            },
             result => {
                 board.put_unchecked(mv, player.to_stone());
                 Ok(match result {
@@ -45,17 +81,49 @@ pub trait Rule: Debug + Send + Sync {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub enum PutOutcome {
     Continue,
     Win,
     Draw,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum PutError {
     Occupied,
-    Invalid,
+    Invalid(Forbidden),
+}
+
+/// reason of the invalid move
+#[derive(Debug, Clone, PartialEq)]
+pub struct Forbidden {
+    pub kind: ForbiddenKind,
+    /// stones of each line forming the pattern, including the move
+    pub lines: Vec<Vec<Move>>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ForbiddenKind {
+    DoubleThree,
+    DoubleFour,
+    Overline,
+    /// not made by a pattern (e.g., opening placement)
+    Other,
+}
+
+impl Forbidden {
+    pub fn other() -> Self {
+        Self { kind: ForbiddenKind::Other, lines: Vec::new() }
+    }
+
+    fn from_lines<'a>(
+        kind: ForbiddenKind, lines: impl Iterator<Item = &'a Line>
+    ) -> Self {
+        Self {
+            kind,
+            lines: lines.map(|line| line.stones(true)).collect(),
+        }
+    }
 }
 
 pub enum CheckResult {
@@ -65,29 +133,34 @@ pub enum CheckResult {
     Draw
 }
 
-const DIRECTION: [Direction; 4] = [
-    Direction::Horizontal, Direction::Vertical, 
-    Direction::DiagDown, Direction::DiagUp,
-];
+/// Wraps a rule with forbidden moves,
+/// the game is also drawn when the side to move has no legal move.
+/// This checks every empty cell after each move, so it's slow for search.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct NoMoveDraw<R: Rule>(pub R);
 
-enum Direction {
-    Horizontal, Vertical, DiagDown, DiagUp,
-}
+impl<R: Rule> Rule for NoMoveDraw<R> {
+    fn is_valid(&self, board: &Board, mv: Move, player: Player) -> bool {
+        self.0.is_valid(board, mv, player)
+    }
 
-impl Direction {
-    pub fn delta(&self) -> (i32, i32) {
-        match self {
-            Direction::Horizontal => (1, 0),
-            Direction::Vertical => (0, 1),
-            Direction::DiagDown => (1, 1),
-            Direction::DiagUp => (1, -1),
-        }
+    fn is_winning(&self, board: &Board, mv: Move, player: Player) -> bool {
+        self.0.is_winning(board, mv, player)
     }
-}
 
-#[derive(PartialEq)]
-enum OpenType {
-    Open, HalfOpen, Closed
+    fn forbidden(&self, board: &Board, mv: Move, player: Player) -> Option<Forbidden> {
+        self.0.forbidden(board, mv, player)
+    }
+
+    fn is_draw(&self, board: &Board, mv: Move, player: Player) -> bool {
+        if self.0.is_draw(board, mv, player) {
+            return true;
+        }
+
+        let mut next = *board;
+        next.put_unchecked(mv, player.to_stone());
+        !self.0.has_legal_move(&next, player.next())
+    }
 }
 
 /// (Omok Rule)
@@ -96,78 +169,176 @@ enum OpenType {
 #[derive(PartialEq, Debug, Clone, Copy)]
 pub struct OmokRule;
 
-impl OmokRule {
-    fn count_one_side(  // helper function
-        board: &Board, mv: Move, stone: Stone,
-        dx: i32, dy: i32
-    ) -> (u32, bool) {
-        let mut point = mv.clone();
-        let mut cnt = 0;
-        let mut open = false;
-        loop {
-            let shifted = point.shift(dx, dy);
-            match shifted {
-                Some(mv) => point = mv,
-                None => break   // breaks when out of board
-            }
-            let item = board.get(point);
-            match item {
-                s if s == stone => cnt += 1,
-                Stone::None => {
-                    open = true;
-                    break;
-                }
-                _ => {
-                    break;
-                }
-            }
+impl Rule for OmokRule {
+    fn is_valid(&self, board: &Board, mv: Move, player: Player) -> bool {
+        self.forbidden(board, mv, player).is_none()
+    }
+
+    fn forbidden(&self, board: &Board, mv: Move, player: Player) -> Option<Forbidden> {
+        let lines = Line::all(board, mv, player.to_stone());
+        let patterns = lines.map(|line| line.classify(true));
+
+        // five has priority over 3-3
+        if patterns.contains(&Pattern::Five) {
+            return None;
+        }
+
+        // 3-3 detection
+        let threes = patterns.iter()
+            .filter(|&&p| p == Pattern::OpenThree)
+            .count();
+        if threes < 2 {
+            return None;
         }
-        (cnt, open)
+
+        let three_lines = lines.iter()
+            .zip(patterns)
+            .filter(|(_, p)| *p == Pattern::OpenThree)
+            .map(|(line, _)| line);
+        Some(Forbidden::from_lines(ForbiddenKind::DoubleThree, three_lines))
+    }
+
+    fn is_winning(&self, board: &Board, mv: Move, player: Player) -> bool {
+        Line::all(board, mv, player.to_stone())
+            .iter()
+            .any(|line| line.is_five(true))
     }
+}
+
+/// (Renju Rule)
+/// black: disallowed 3-3, 4-4 and jangmok, only exact five wins
+/// white: no restriction, five or more wins
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct RenjuRule;
 
-    fn line_count(
-        board: &Board, mv: Move, player: Player, 
-        dx: i32, dy: i32
-    ) -> (u32, OpenType) {
-        let stone = player.to_stone();
+impl RenjuRule {
+    /// checks forbidden move for black, assuming mv is empty
+    fn forbidden_black(board: &Board, mv: Move) -> Option<Forbidden> {
+        let lines = Line::all(board, mv, Stone::Black);
+        let patterns = lines.map(|line| line.classify(true));
 
-        let (cnt1, open1) = Self::count_one_side(board, mv, stone, dx, dy);
-        let (cnt2, open2) = Self::count_one_side(board, mv, stone, -dx, -dy);
-        
-        let open_type =
-            if open1 && open2 { OpenType::Open }
-            else if open1 ^ open2 { OpenType::HalfOpen }
-            else { OpenType::Closed };
-        
-        (cnt1 + cnt2 + 1, open_type)
+        // five has priority over every forbidden pattern
+        if patterns.contains(&Pattern::Five) {
+            return None;
+        }
+
+        if patterns.contains(&Pattern::Overline) {
+            let overlines = lines.iter()
+                .zip(patterns)
+                .filter(|(_, p)| *p == Pattern::Overline)
+                .map(|(line, _)| line);
+            return Some(Forbidden::from_lines(ForbiddenKind::Overline, overlines));
+        }
+
+        let fours: usize = patterns.iter().map(|p| p.fours()).sum();
+        if fours >= 2 {
+            let four_lines = lines.iter()
+                .zip(patterns)
+                .filter(|(_, p)| p.fours() > 0)
+                .map(|(line, _)| line);
+            return Some(Forbidden::from_lines(ForbiddenKind::DoubleFour, four_lines));
+        }
+
+        let three_lines: Vec<&Line> = lines.iter()
+            .zip(patterns)
+            .filter(|(line, p)| {
+                *p == Pattern::OpenThree
+                    && Self::is_real_three(board, mv, line)
+            })
+            .map(|(line, _)| line)
+            .collect();
+        if three_lines.len() >= 2 {
+            return Some(Forbidden::from_lines(
+                ForbiddenKind::DoubleThree, three_lines.into_iter()
+            ));
+        }
+
+        None
+    }
+
+    /// three is real only if it can become a straight four
+    /// by a move which is not forbidden itself
+    fn is_real_three(board: &Board, mv: Move, line: &Line) -> bool {
+        let mut next = *board;
+        next.put_unchecked(mv, Stone::Black);
+        line.three_points(true)
+            .into_iter()
+            .any(|p| Self::forbidden_black(&next, p).is_none())
     }
 }
 
-impl Rule for OmokRule {
+impl Rule for RenjuRule {
     fn is_valid(&self, board: &Board, mv: Move, player: Player) -> bool {
-        // 3-3 deteciton
-        let mut already_sam = false;
-        for (dx, dy) in DIRECTION.map(|x| x.delta()) {
-            let (cnt, open) = Self::line_count(board, mv, player, dx, dy);
-            if cnt == 3 && open == OpenType::Open {
-                if !already_sam {
-                    already_sam = true;
-                }
-                else {
-                    return false;
-                }
-            }
+        self.forbidden(board, mv, player).is_none()
+    }
+
+    fn forbidden(&self, board: &Board, mv: Move, player: Player) -> Option<Forbidden> {
+        match player {
+            Player::Black => Self::forbidden_black(board, mv),
+            Player::White => None,
         }
+    }
+
+    fn is_winning(&self, board: &Board, mv: Move, player: Player) -> bool {
+        // jangmok is winning only for white
+        let exact = player == Player::Black;
+        Line::all(board, mv, player.to_stone())
+            .iter()
+            .any(|line| line.is_five(exact))
+    }
+}
+
+/// (Freestyle Gomoku Rule)
+/// no forbidden move, five or more wins
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct FreestyleRule;
+
+impl Rule for FreestyleRule {
+    fn is_valid(&self, _board: &Board, _mv: Move, _player: Player) -> bool {
         true
     }
 
     fn is_winning(&self, board: &Board, mv: Move, player: Player) -> bool {
-        for (dx, dy) in DIRECTION.map(|x| x.delta()) {
-            let (cnt, _) = Self::line_count(board, mv, player, dx, dy);
-            if cnt == 5 {
-                return true;
-            }
-        }
-        false
+        Line::all(board, mv, player.to_stone())
+            .iter()
+            .any(|line| line.is_five(false))
+    }
+}
+
+/// (Standard Gomoku Rule)
+/// no forbidden move, only exact five wins
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct StandardGomokuRule;
+
+impl Rule for StandardGomokuRule {
+    fn is_valid(&self, _board: &Board, _mv: Move, _player: Player) -> bool {
+        true
+    }
+
+    fn is_winning(&self, board: &Board, mv: Move, player: Player) -> bool {
+        Line::all(board, mv, player.to_stone())
+            .iter()
+            .any(|line| line.is_five(true))
+    }
+}
+
+/// (Caro Rule)
+/// no forbidden move, five or more wins
+/// but five blocked at both ends by opponent stones is not winning
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct CaroRule;
+
+impl Rule for CaroRule {
+    fn is_valid(&self, _board: &Board, _mv: Move, _player: Player) -> bool {
+        true
+    }
+
+    fn is_winning(&self, board: &Board, mv: Move, player: Player) -> bool {
+        Line::all(board, mv, player.to_stone())
+            .iter()
+            .any(|line| {
+                line.is_five(false)
+                    && line.open_type_by_stone(board) != OpenType::Closed
+            })
     }
 }
\ No newline at end of file
@@ -0,0 +1,50 @@
+use super::board::{Move, MAX_SIZE};
+
+// Zobrist keys of the board position.
+// Keys are made at compile time from a fixed seed,
+// so hashes are the same in every run and can be stored
+// (opening books, position databases).
+
+const SEED: u64 = 0x6f6d_6f6b_2d62_616d;
+
+const CELLS: usize = MAX_SIZE * MAX_SIZE;
+
+// [black cells][white cells][side to move][board sizes]
+const KEYS: [u64; 2 * CELLS + 1 + MAX_SIZE + 1] = keys(SEED);
+
+/// splitmix64, returns the next state and the output
+const fn splitmix(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    (state, z ^ (z >> 31))
+}
+
+const fn keys<const N: usize>(seed: u64) -> [u64; N] {
+    let mut keys = [0; N];
+    let mut state = seed;
+    let mut i = 0;
+    while i < N {
+        let (next, key) = splitmix(state);
+        keys[i] = key;
+        state = next;
+        i += 1;
+    }
+    keys
+}
+
+/// key of a stone, color is 0 for black and 1 for white
+pub fn stone(mv: Move, color: usize) -> u64 {
+    KEYS[color * CELLS + mv.y * MAX_SIZE + mv.x]
+}
+
+/// toggled every move, set when white is to move
+pub fn side() -> u64 {
+    KEYS[2 * CELLS]
+}
+
+/// key of the blank board, boards of different sizes never share positions
+pub fn blank(size: usize) -> u64 {
+    KEYS[2 * CELLS + 1 + size]
+}
\ No newline at end of file
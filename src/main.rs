@@ -1,7 +1,8 @@
 pub mod bot;
 pub mod core;
 pub mod ui;
-pub mod test;
+#[cfg(test)]
+mod test;
 
 use tracing_subscriber::prelude::*;
 use tracing_subscriber::fmt;
@@ -1,11 +1,47 @@
+use crate::bot::eval::PatternEval;
+use crate::bot::model::NegamaxModel;
+use crate::bot::prune::NeighborPrune;
+use crate::core::board::{Board, Move, Stone};
+use crate::core::rule::OmokRule;
+
+/// black stones, then white stones on the 15x15 board
+fn board_with(black: &[(usize, usize)], white: &[(usize, usize)]) -> Board {
+    let mut board = Board::blank();
+    for &(x, y) in black {
+        board.put(Move { x, y }, Stone::Black);
+    }
+    for &(x, y) in white {
+        board.put(Move { x, y }, Stone::White);
+    }
+    board
+}
+
+/// moves put in turn from black on the size x size board,
+/// with the last move (the center if there is none)
+fn played(size: usize, moves: &[(usize, usize)]) -> (Board, Move) {
+    let mut board = Board::new(size);
+    for &(x, y) in moves {
+        let stone = board.turn().to_stone();
+        board.put(Move { x, y }, stone);
+    }
+    let last = moves.last().map_or(board.center(), |&(x, y)| Move { x, y });
+    (board, last)
+}
+
+/// negamax bot with the pattern evaluation under the omok rule
+fn omok_model() -> NegamaxModel<PatternEval<OmokRule>, NeighborPrune, OmokRule> {
+    NegamaxModel::new(PatternEval { rule: OmokRule }, NeighborPrune, OmokRule)
+}
+
 #[allow(unused_imports)]
 mod negamax {
     use std::sync::Arc;
 
-    use crate::bot::model::Model;
+    use crate::bot::model::{Model, SearchLimits};
     use crate::core::board::{Board, Move, Stone};
     use crate::bot::{model, eval, prune};
     use crate::core::rule;
+    use super::{board_with, omok_model, played};
     // use crate::bot::prune::*;
 
     #[test]
@@ -27,12 +63,7 @@ mod negamax {
         super::super::init_trace();
         let mut board = Board::blank();
 
-        let model = model::NegamaxModel {
-            depth: 4,
-            eval: eval::BaboEval { rule: Arc::new(rule::OmokRule) },
-            prune: prune::NeighborPrune,
-            rule: rule::OmokRule,
-        };
+        let mut model = omok_model();
 
         board.put(Move { x: 7, y: 7 }, Stone::Black);
         board.put(Move { x: 6, y: 6 }, Stone::White);
@@ -47,7 +78,1555 @@ mod negamax {
 
         // println!("{:?}", possible);
 
-        let next = model.next_move(&mut board, Move { x: 6, y: 5 });
+        let next = model.next_move(&board, Move { x: 6, y: 5 }, SearchLimits::depth(4));
         let _ = next;
     }
+
+    #[test]
+    fn renju_bot() {
+        let mut board = Board::blank();
+
+        let mut model = model::NegamaxModel::new(
+            eval::PatternEval { rule: rule::RenjuRule },
+            prune::NeighborPrune,
+            rule::RenjuRule,
+        );
+
+        board.put(Move { x: 7, y: 7 }, Stone::Black);
+        board.put(Move { x: 6, y: 6 }, Stone::White);
+
+        board.put(Move { x: 7, y: 6 }, Stone::Black);
+
+        let next = model.next_move(&board, Move { x: 7, y: 6 }, SearchLimits::depth(3));
+        assert!(next.is_some());
+    }
+
+    #[test]
+    fn budget() {
+        use std::time::Duration;
+        let secs = Duration::from_secs;
+
+        assert_eq!(SearchLimits::depth(5).budget(), None);
+        assert_eq!(SearchLimits::move_time(secs(2)).budget(), Some(secs(2)));
+        // 1/20 of the clock plus the increment
+        assert_eq!(SearchLimits::clock(secs(60), secs(1)).budget(), Some(secs(4)));
+        // never more than half of the clock
+        assert_eq!(SearchLimits::clock(secs(4), secs(10)).budget(), Some(secs(2)));
+
+        let mut limits = SearchLimits::clock(secs(60), secs(0));
+        limits.move_time = Some(secs(1));
+        assert_eq!(limits.budget(), Some(secs(1)));
+    }
+
+    #[test]
+    fn time_limit() {
+        use std::time::{Duration, Instant};
+
+        let (board, last) = played(15, &[(7, 7), (8, 8), (6, 8)]);
+        let mut model = omok_model();
+
+        // too deep to finish, the last completed iteration is used
+        let start = Instant::now();
+        let limits = SearchLimits::move_time(Duration::from_millis(300));
+        let next = model.next_move(&board, last, limits);
+        assert!(next.is_some());
+        assert!(start.elapsed() < Duration::from_secs(3));
+    }
+
+    #[test]
+    fn cancel() {
+        use std::thread;
+        use std::time::{Duration, Instant};
+
+        let (board, last) = played(15, &[(7, 7), (8, 8), (6, 8)]);
+        let mut model = omok_model();
+
+        // stopped before the start, no move is searched
+        let limits = SearchLimits::depth(model::MAX_DEPTH);
+        limits.stop.stop();
+        assert_eq!(model.next_move(&board, last, limits), None);
+
+        // stopped from another thread, without any time limit
+        let limits = SearchLimits::depth(model::MAX_DEPTH);
+        let stop = limits.stop.clone();
+        let start = Instant::now();
+        let search = thread::spawn(move || {
+            model.next_move(&board, last, limits)
+        });
+        thread::sleep(Duration::from_millis(200));
+        stop.stop();
+        let next = search.join().unwrap();
+        assert!(next.is_some());
+        assert!(start.elapsed() < Duration::from_secs(3));
+    }
+
+    #[test]
+    fn search_info() {
+        use std::sync::mpsc;
+
+        let (board, last) = played(15, &[(7, 7), (8, 8), (6, 8)]);
+        let mut model = omok_model();
+
+        let (tx, rx) = mpsc::channel();
+        let mut limits = SearchLimits::depth(3);
+        limits.info = Some(tx);
+        let next = model.next_move(&board, last, limits).unwrap();
+
+        let infos: Vec<model::SearchInfo> = rx.try_iter().collect();
+        assert_eq!(infos.iter().map(|i| i.depth).collect::<Vec<_>>(), vec![1, 2, 3]);
+        let last = infos.last().unwrap();
+        assert_eq!(last.pv.first(), Some(&next));
+        assert!(last.pv.len() <= 3);
+        assert!(infos.windows(2).all(|w| w[0].stats.nodes <= w[1].stats.nodes));
+    }
+
+    #[test]
+    fn search_stats() {
+        use std::thread;
+
+        let (board, last) = played(15, &[(7, 7), (8, 8), (6, 8)]);
+        let search = move || {
+            let mut model = omok_model();
+            model.search(&board, last, SearchLimits::depth(3))
+        };
+
+        // searches at the same time don't share counters
+        let alone = search();
+        let handles: Vec<_> = (0..2).map(|_| thread::spawn(search)).collect();
+        for handle in handles {
+            let result = handle.join().unwrap();
+            assert!(result.best.is_some());
+            assert_eq!(result.depth, 3);
+            assert_eq!(result.stats.max_depth, 3);
+            assert_eq!(result.stats, alone.stats);
+        }
+
+        let stats = alone.stats;
+        assert!(stats.nodes > 0 && stats.eval_calls > 0 && stats.cutoffs > 0);
+        assert!(stats.expanded > 0 && stats.candidates > stats.expanded);
+        assert!(stats.branching_factor() > 1.0);
+    }
+
+    #[test]
+    fn win_ends_deepening() {
+        let board = board_with(&[(3, 7), (4, 7), (5, 7), (6, 7)], &[(3, 9), (4, 9), (5, 9), (6, 9)]);
+        let mut model = omok_model();
+
+        // no time limit and the maximum depth, but the win is found at depth 1
+        let limits = SearchLimits::depth(model::MAX_DEPTH);
+        let next = model.next_move(&board, Move { x: 6, y: 9 }, limits).unwrap();
+        assert!(next == Move { x: 2, y: 7 } || next == Move { x: 7, y: 7 });
+    }
+
+    /// tied moves are picked by the seed, the same seed plays the same
+    #[test]
+    fn seeded() {
+        let (board, center) = played(15, &[(7, 7)]);
+
+        let next = |seed| {
+            let mut model = model::NegamaxModel::with_seed(
+                eval::PatternEval { rule: rule::OmokRule },
+                prune::NeighborPrune,
+                rule::OmokRule,
+                seed,
+            );
+            assert_eq!(model.seed(), seed);
+            model.next_move(&board, center, SearchLimits::depth(2)).unwrap()
+        };
+
+        let moves: Vec<Move> = (0..16).map(next).collect();
+        assert_eq!(moves, (0..16).map(next).collect::<Vec<_>>());
+        assert!(moves.iter().any(|&mv| mv != moves[0]));
+    }
+
+    /// narrower windows only save nodes, the value is the same
+    #[test]
+    fn pvs_same_score() {
+        let positions: [&[(usize, usize)]; 3] = [
+            &[(7, 7), (8, 8), (7, 8)],
+            &[(7, 7), (8, 6), (7, 8), (9, 6), (6, 6)],
+            &[(7, 7), (6, 8), (8, 8), (9, 9), (8, 6), (6, 6)],
+        ];
+        let mut nodes = [0, 0];
+        for moves in positions {
+            let (board, last) = played(15, moves);
+
+            let scores = [false, true].map(|enabled| {
+                let mut model = omok_model();
+                model.pvs = enabled;
+                model.aspiration = enabled;
+                let result = model.search(&board, last, SearchLimits::depth(4));
+                nodes[enabled as usize] += result.stats.nodes;
+                result.score
+            });
+            assert_eq!(scores[0], scores[1]);
+        }
+        assert!(nodes[1] < nodes[0], "{:?}", nodes);
+    }
+
+    /// helpers share the table, the calling thread still decides the move
+    #[test]
+    fn lazy_smp() {
+        use std::thread;
+        use std::time::{Duration, Instant};
+
+        let mut board = board_with(&[(3, 7), (4, 7), (5, 7), (6, 7)], &[(3, 9), (4, 9), (5, 9), (6, 9)]);
+        let mut model = omok_model();
+        model.threads = 4;
+
+        let result = model.search(&board, Move { x: 6, y: 9 }, SearchLimits::depth(3));
+        let next = result.best.unwrap();
+        assert!(next == Move { x: 2, y: 7 } || next == Move { x: 7, y: 7 });
+
+        // every thread stops with the calling thread
+        let limits = SearchLimits::depth(model::MAX_DEPTH);
+        let stop = limits.stop.clone();
+        board.undo_unchecked(Move { x: 6, y: 9 });
+        board.undo_unchecked(Move { x: 6, y: 7 });
+        let start = Instant::now();
+        let search = thread::spawn(move || {
+            model.search(&board, Move { x: 5, y: 9 }, limits)
+        });
+        thread::sleep(Duration::from_millis(200));
+        stop.stop();
+        let result = search.join().unwrap();
+        assert!(result.best.is_some());
+        assert!(start.elapsed() < Duration::from_secs(3));
+    }
+}
+
+mod renju {
+    use crate::core::board::{Move, Player};
+    use crate::core::rule::{Rule, RenjuRule, PutError, ForbiddenKind};
+    use super::board_with;
+
+    #[test]
+    fn double_three_black_only() {
+        let board = board_with(&[(7, 5), (7, 6), (5, 7), (6, 7)], &[]);
+        let mv = Move { x: 7, y: 7 };
+        assert!(!RenjuRule.is_valid(&board, mv, Player::Black));
+
+        let board = board_with(&[], &[(7, 5), (7, 6), (5, 7), (6, 7)]);
+        assert!(RenjuRule.is_valid(&board, mv, Player::White));
+    }
+
+    #[test]
+    fn double_four() {
+        let board = board_with(&[(4, 7), (5, 7), (6, 7), (7, 4), (7, 5), (7, 6)], &[]);
+        assert!(!RenjuRule.is_valid(&board, Move { x: 7, y: 7 }, Player::Black));
+    }
+
+    #[test]
+    fn double_four_in_line() {
+        // X _ X X [ ] _ X
+        let board = board_with(&[(1, 7), (3, 7), (4, 7), (7, 7)], &[]);
+        assert!(!RenjuRule.is_valid(&board, Move { x: 5, y: 7 }, Player::Black));
+    }
+
+    #[test]
+    fn four_three_allowed() {
+        let board = board_with(&[(4, 7), (5, 7), (6, 7), (7, 5), (7, 6)], &[]);
+        assert!(RenjuRule.is_valid(&board, Move { x: 7, y: 7 }, Player::Black));
+    }
+
+    #[test]
+    fn overline() {
+        let stones = [(2, 7), (3, 7), (4, 7), (6, 7), (7, 7)];
+        let mv = Move { x: 5, y: 7 };
+
+        let board = board_with(&stones, &[]);
+        assert!(!RenjuRule.is_valid(&board, mv, Player::Black));
+        assert!(!RenjuRule.is_winning(&board, mv, Player::Black));
+
+        let board = board_with(&[], &stones);
+        assert!(RenjuRule.is_valid(&board, mv, Player::White));
+        assert!(RenjuRule.is_winning(&board, mv, Player::White));
+    }
+
+    #[test]
+    fn five_over_forbidden() {
+        // five with double three at the same time
+        let board = board_with(
+            &[(3, 7), (4, 7), (5, 7), (6, 7), (7, 5), (7, 6), (5, 5), (6, 6)],
+            &[],
+        );
+        let mv = Move { x: 7, y: 7 };
+        assert!(RenjuRule.is_valid(&board, mv, Player::Black));
+        assert!(RenjuRule.is_winning(&board, mv, Player::Black));
+    }
+
+    #[test]
+    fn forbidden_reason() {
+        let mut board = board_with(&[(7, 5), (7, 6), (4, 7), (5, 7)], &[]);
+        let mv = Move { x: 7, y: 7 };
+
+        let forbidden = RenjuRule.forbidden(&board, mv, Player::Black).unwrap();
+        assert_eq!(forbidden.kind, ForbiddenKind::DoubleThree);
+        assert_eq!(forbidden.lines, vec![
+            vec![Move { x: 4, y: 7 }, Move { x: 5, y: 7 }, mv],
+            vec![Move { x: 7, y: 5 }, Move { x: 7, y: 6 }, mv],
+        ]);
+
+        match RenjuRule.put(&mut board, mv, Player::Black) {
+            Err(PutError::Invalid(reason)) => assert_eq!(reason, forbidden),
+            _ => panic!("forbidden move is put"),
+        }
+        assert!(RenjuRule.forbidden(&board, mv, Player::White).is_none());
+    }
+
+    #[test]
+    fn forbidden_kinds() {
+        let board = board_with(&[(4, 7), (5, 7), (6, 7), (7, 4), (7, 5), (7, 6)], &[]);
+        let forbidden = RenjuRule.forbidden(&board, Move { x: 7, y: 7 }, Player::Black);
+        assert_eq!(forbidden.unwrap().kind, ForbiddenKind::DoubleFour);
+
+        let board = board_with(&[(2, 7), (3, 7), (4, 7), (6, 7), (7, 7)], &[]);
+        let forbidden = RenjuRule.forbidden(&board, Move { x: 5, y: 7 }, Player::Black).unwrap();
+        assert_eq!(forbidden.kind, ForbiddenKind::Overline);
+        assert_eq!(forbidden.lines[0].len(), 6);
+    }
+
+    #[test]
+    fn three_blocked_by_overline() {
+        // X _ _ X X [ ] _ _ X cannot become a straight four
+        let board = board_with(
+            &[(1, 7), (4, 7), (5, 7), (9, 7), (6, 5), (6, 6)],
+            &[],
+        );
+        assert!(RenjuRule.is_valid(&board, Move { x: 6, y: 7 }, Player::Black));
+    }
+
+    #[test]
+    fn three_blocked_by_forbidden_point() {
+        // both points making a straight four horizontally are overlines
+        let board = board_with(
+            &[
+                (5, 7), (6, 7), (7, 5), (7, 6),
+                (4, 4), (4, 5), (4, 6), (4, 8), (4, 9),
+                (8, 4), (8, 5), (8, 6), (8, 8), (8, 9),
+            ],
+            &[],
+        );
+        assert!(RenjuRule.is_valid(&board, Move { x: 7, y: 7 }, Player::Black));
+    }
+}
+
+mod pattern {
+    use crate::core::board::{Board, Move, Player, Stone};
+    use crate::core::pattern::{Direction, Line, OpenType, Pattern};
+    use crate::core::rule::{Rule, OmokRule};
+    use super::board_with;
+
+    fn classify(board: &Board, x: usize) -> Pattern {
+        Line::new(board, Move { x, y: 7 }, Stone::Black, Direction::Horizontal)
+            .classify(true)
+    }
+
+    #[test]
+    fn broken_three() {
+        // X X _ [X]
+        assert_eq!(classify(&board_with(&[(4, 7), (5, 7)], &[]), 7), Pattern::OpenThree);
+        // X _ [X] X
+        assert_eq!(classify(&board_with(&[(4, 7), (7, 7)], &[]), 6), Pattern::OpenThree);
+        // O X X [X] _
+        assert_eq!(classify(&board_with(&[(5, 7), (6, 7)], &[(4, 7)]), 7), Pattern::Three);
+    }
+
+    #[test]
+    fn broken_four() {
+        // X _ X X [X]
+        let board = board_with(&[(3, 7), (5, 7), (6, 7)], &[]);
+        assert_eq!(classify(&board, 7), Pattern::Four);
+
+        let line = Line::new(&board, Move { x: 7, y: 7 }, Stone::Black, Direction::Horizontal);
+        let points: Vec<(usize, usize)> = line.four_points(true)
+            .iter()
+            .map(|p| (p.x, p.y))
+            .collect();
+        assert_eq!(points, vec![(4, 7)]);
+    }
+
+    #[test]
+    fn four_kinds() {
+        // _ X X X [X] _
+        assert_eq!(classify(&board_with(&[(4, 7), (5, 7), (6, 7)], &[]), 7), Pattern::OpenFour);
+        // X _ X [X] X _ X
+        assert_eq!(classify(&board_with(&[(3, 7), (5, 7), (7, 7), (9, 7)], &[]), 6), Pattern::DoubleFour);
+        // X X X [X] X X
+        assert_eq!(classify(&board_with(&[(2, 7), (3, 7), (4, 7), (6, 7), (7, 7)], &[]), 5), Pattern::Overline);
+    }
+
+    #[test]
+    fn open_type_out_of_cells() {
+        // _ X X X X X [X] _, the run reaches the first copied cell
+        let board = board_with(&[(1, 7), (2, 7), (3, 7), (4, 7), (5, 7)], &[]);
+        let line = Line::new(&board, Move { x: 6, y: 7 }, Stone::Black, Direction::Horizontal);
+        assert_eq!(line.open_type(), OpenType::HalfOpen);
+        assert_eq!(line.open_type_by_stone(&board), OpenType::Open);
+    }
+
+    #[test]
+    fn omok_split_double_three() {
+        let board = board_with(&[], &[(7, 4), (7, 5), (5, 7), (6, 7)]);
+        assert!(!OmokRule.is_valid(&board, Move { x: 7, y: 7 }, Player::White));
+    }
+
+    #[test]
+    fn omok_double_four_allowed() {
+        let board = board_with(&[(7, 3), (7, 5), (7, 6), (4, 7), (5, 7), (6, 7)], &[]);
+        assert!(OmokRule.is_valid(&board, Move { x: 7, y: 7 }, Player::Black));
+    }
+}
+
+mod gomoku {
+    use crate::bot::model::{Model, NegamaxModel, SearchLimits};
+    use crate::bot::{eval, prune};
+    use crate::core::board::{Move, Player};
+    use crate::core::rule::{Rule, FreestyleRule, StandardGomokuRule};
+    use super::board_with;
+
+    /// black X X X _ X X on row 7, (5, 7) makes six
+    const OVERLINE: [(usize, usize); 5] = [(2, 7), (3, 7), (4, 7), (6, 7), (7, 7)];
+    const TOP_ROW: [(usize, usize); 5] = [(0, 0), (2, 0), (4, 0), (6, 0), (8, 0)];
+
+    #[test]
+    fn overline() {
+        let board = board_with(&OVERLINE, &TOP_ROW);
+        let mv = Move { x: 5, y: 7 };
+        assert!(FreestyleRule.is_winning(&board, mv, Player::Black));
+        assert!(!StandardGomokuRule.is_winning(&board, mv, Player::Black));
+    }
+
+    #[test]
+    fn no_forbidden_move() {
+        let board = board_with(&[(7, 5), (7, 6), (5, 7), (6, 7), (4, 7), (7, 4)], &[]);
+        let mv = Move { x: 7, y: 7 };
+        assert!(FreestyleRule.is_valid(&board, mv, Player::Black));
+        assert!(StandardGomokuRule.is_valid(&board, mv, Player::Black));
+    }
+
+    #[test]
+    fn freestyle_bot() {
+        let board = board_with(&OVERLINE, &TOP_ROW);
+        let mut model = NegamaxModel::new(
+            eval::PatternEval { rule: FreestyleRule },
+            prune::NeighborPrune,
+            FreestyleRule,
+        );
+        let next = model.next_move(&board, Move { x: 8, y: 0 }, SearchLimits::depth(1)).unwrap();
+        assert_eq!((next.x, next.y), (5, 7));
+    }
+}
+
+mod caro {
+    use crate::core::board::{Move, Player};
+    use crate::core::rule::{Rule, CaroRule};
+    use super::board_with;
+
+    #[test]
+    fn blocked_five() {
+        let black = [(2, 7), (3, 7), (4, 7), (5, 7)];
+        let mv = Move { x: 6, y: 7 };
+
+        // O X X X X [X] O
+        let board = board_with(&black, &[(1, 7), (7, 7)]);
+        assert!(!CaroRule.is_winning(&board, mv, Player::Black));
+
+        // O X X X X [X] _
+        let board = board_with(&black, &[(1, 7)]);
+        assert!(CaroRule.is_winning(&board, mv, Player::Black));
+    }
+
+    #[test]
+    fn blocked_overline() {
+        let black = [(2, 7), (3, 7), (4, 7), (5, 7), (7, 7)];
+        let mv = Move { x: 6, y: 7 };
+
+        let board = board_with(&black, &[(1, 7), (8, 7)]);
+        assert!(!CaroRule.is_winning(&board, mv, Player::Black));
+
+        let board = board_with(&black, &[]);
+        assert!(CaroRule.is_winning(&board, mv, Player::Black));
+    }
+
+    #[test]
+    fn edge_is_not_blocking() {
+        // | X X X X [X] O
+        let board = board_with(&[(0, 7), (1, 7), (2, 7), (3, 7)], &[(5, 7)]);
+        assert!(CaroRule.is_winning(&board, Move { x: 4, y: 7 }, Player::Black));
+
+        // | [X] X X X X O
+        let board = board_with(&[(1, 7), (2, 7), (3, 7), (4, 7)], &[(5, 7)]);
+        assert!(CaroRule.is_winning(&board, Move { x: 0, y: 7 }, Player::Black));
+
+        // diagonal from the corner
+        let board = board_with(&[(10, 10), (11, 11), (12, 12), (13, 13)], &[(9, 9)]);
+        assert!(CaroRule.is_winning(&board, Move { x: 14, y: 14 }, Player::Black));
+    }
+
+    #[test]
+    fn edge_and_opponent_on_both_sides() {
+        // | O X X X X [X] O
+        let board = board_with(&[(1, 7), (2, 7), (3, 7), (4, 7)], &[(0, 7), (6, 7)]);
+        assert!(!CaroRule.is_winning(&board, Move { x: 5, y: 7 }, Player::Black));
+    }
+
+    #[test]
+    fn run_past_copied_cells() {
+        // O X X X X X [X] O, the run is longer than the cells copied around the move
+        let black = [(1, 7), (2, 7), (3, 7), (4, 7), (5, 7)];
+        let mv = Move { x: 6, y: 7 };
+        let board = board_with(&black, &[(0, 7), (7, 7)]);
+        assert!(!CaroRule.is_winning(&board, mv, Player::Black));
+
+        // _ X X X X X [X] O
+        let board = board_with(&black, &[(7, 7)]);
+        assert!(CaroRule.is_winning(&board, mv, Player::Black));
+    }
+}
+
+
+mod draw {
+    use crate::bot::model::{Model, NegamaxModel, SearchLimits};
+    use crate::bot::{eval, prune};
+    use crate::core::board::{Board, Move, Player, Stone};
+    use crate::core::rule::{
+        Rule, PutOutcome, NoMoveDraw, OmokRule, FreestyleRule,
+    };
+
+    /// every cell but the last corner is filled without any five
+    pub(super) fn almost_full(size: usize) -> Board {
+        let mut board = Board::new(size);
+        for y in 0..size {
+            for x in 0..size {
+                if (x, y) == (size - 1, size - 1) {
+                    continue;
+                }
+                let stone = if (x / 2 + y) % 2 == 0 { Stone::Black } else { Stone::White };
+                board.put(Move { x, y }, stone);
+            }
+        }
+        board
+    }
+
+    #[derive(Debug)]
+    struct WhiteCannotMove;
+
+    impl Rule for WhiteCannotMove {
+        fn is_valid(&self, _board: &Board, _mv: Move, player: Player) -> bool {
+            player == Player::Black
+        }
+
+        fn is_winning(&self, _board: &Board, _mv: Move, _player: Player) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn full_board() {
+        let mv = Move { x: 14, y: 14 };
+
+        let mut board = almost_full(15);
+        let outcome = FreestyleRule.put(&mut board, mv, Player::Black);
+        assert!(matches!(outcome, Ok(PutOutcome::Draw)));
+
+        let mut board = almost_full(15);
+        let outcome = OmokRule.put(&mut board, mv, Player::Black);
+        assert!(matches!(outcome, Ok(PutOutcome::Draw)));
+    }
+
+    #[test]
+    fn no_legal_move() {
+        let mut board = Board::blank();
+        let mv = Move { x: 7, y: 7 };
+
+        let outcome = WhiteCannotMove.put(&mut board.clone(), mv, Player::Black);
+        assert!(matches!(outcome, Ok(PutOutcome::Continue)));
+
+        let outcome = NoMoveDraw(WhiteCannotMove).put(&mut board, mv, Player::Black);
+        assert!(matches!(outcome, Ok(PutOutcome::Draw)));
+    }
+
+    #[test]
+    fn search_full_board() {
+        let board = almost_full(15);
+        let mut model = NegamaxModel::new(
+            eval::PatternEval { rule: FreestyleRule },
+            prune::NeighborPrune,
+            FreestyleRule,
+        );
+        let next = model.next_move(&board, Move { x: 13, y: 14 }, SearchLimits::depth(3)).unwrap();
+        assert_eq!((next.x, next.y), (14, 14));
+    }
+}
+
+mod opening {
+    use crate::bot::model::{Model, NegamaxModel, SearchLimits};
+    use crate::bot::{eval, prune};
+    use crate::core::board::{Board, Move, Player, Stone};
+    use crate::core::opening::{Choice, Opening, OpeningRule, ProRule, Request, Side};
+    use crate::core::rule::{Rule, OmokRule};
+    use super::{board_with, omok_model};
+
+    #[test]
+    fn pro_placement() {
+        let rule = ProRule::pro(OmokRule);
+        let mut board = Board::blank();
+        assert!(!rule.is_valid(&board, Move { x: 6, y: 7 }, Player::Black));
+        assert!(rule.put(&mut board, Move { x: 7, y: 7 }, Player::Black).is_ok());
+        assert!(rule.put(&mut board, Move { x: 8, y: 8 }, Player::White).is_ok());
+
+        assert!(!rule.is_valid(&board, Move { x: 9, y: 5 }, Player::Black));
+        assert!(rule.is_valid(&board, Move { x: 10, y: 5 }, Player::Black));
+
+        let rule = ProRule::long_pro(OmokRule);
+        assert!(!rule.is_valid(&board, Move { x: 10, y: 5 }, Player::Black));
+        assert!(rule.is_valid(&board, Move { x: 11, y: 5 }, Player::Black));
+    }
+
+    #[test]
+    fn swap() {
+        let mut opening = Opening::new(OpeningRule::Swap);
+        let board = Board::blank();
+        assert_eq!(opening.request(&board), Request::Put(Side::First));
+
+        let board = board_with(&[(7, 7), (7, 9)], &[(8, 7)]);
+        assert_eq!(
+            opening.request(&board),
+            Request::Choose(Side::Second, vec![Choice::Black, Choice::White]),
+        );
+        assert!(!opening.choose(&board, Side::First, Choice::Black));
+        assert!(!opening.choose(&board, Side::Second, Choice::PutTwo));
+
+        // second player takes black, first player plays the 4th stone as white
+        assert!(opening.choose(&board, Side::Second, Choice::Black));
+        assert_eq!(opening.color(Side::Second), Player::Black);
+        assert_eq!(opening.request(&board), Request::Put(Side::First));
+    }
+
+    #[test]
+    fn swap2_put_two() {
+        let mut opening = Opening::new(OpeningRule::Swap2);
+        let mut board = board_with(&[(7, 7), (7, 9)], &[(8, 7)]);
+        assert!(opening.choose(&board, Side::Second, Choice::PutTwo));
+        assert_eq!(opening.request(&board), Request::Put(Side::Second));
+
+        board.put(Move { x: 6, y: 6 }, Stone::White);
+        board.put(Move { x: 8, y: 8 }, Stone::Black);
+        assert_eq!(
+            opening.request(&board),
+            Request::Choose(Side::First, vec![Choice::Black, Choice::White]),
+        );
+
+        // first player takes white and plays the 6th stone
+        assert!(opening.choose(&board, Side::First, Choice::White));
+        assert_eq!(opening.color(Side::First), Player::White);
+        assert_eq!(opening.request(&board), Request::Put(Side::First));
+    }
+
+    #[test]
+    fn bot_pro_third_stone() {
+        let rule = ProRule::pro(OmokRule);
+        let mut model = NegamaxModel::new(
+            eval::PatternEval { rule },
+            prune::NeighborPrune,
+            rule,
+        );
+
+        let board = board_with(&[(7, 7)], &[(8, 8)]);
+        let next = model.next_move(&board, Move { x: 8, y: 8 }, SearchLimits::depth(1)).unwrap();
+        assert!(rule.is_valid(&board, next, Player::Black));
+    }
+
+    #[test]
+    fn bot_choice() {
+        let mut model = omok_model();
+
+        // black already has an open three, white should not be chosen
+        let board = board_with(&[(6, 7), (7, 7), (8, 7)], &[(0, 0), (0, 14)]);
+
+        let options = [Choice::Black, Choice::White, Choice::PutTwo];
+        let choice = model.choose(&board, Move { x: 8, y: 7 }, &options, SearchLimits::depth(2));
+        assert_eq!(choice, Choice::Black);
+    }
+}
+
+mod size {
+    use crate::bot::model::{Model, SearchLimits};
+    use crate::core::board::{Board, Move, Player, Stone};
+    use crate::core::pattern::{Direction, Line};
+    use crate::core::rule::{Rule, PutOutcome, FreestyleRule};
+    use super::{draw::almost_full, omok_model};
+
+    #[test]
+    fn bounds() {
+        let board = Board::new(9);
+        assert_eq!(board.center(), Move { x: 4, y: 4 });
+        assert_eq!(board.empty_count(), 81);
+        assert!(Move::new(8, 8, 9).is_some());
+        assert!(Move::new(9, 0, 9).is_none());
+        assert!(Move::new(18, 18, 19).is_some());
+        assert!(Move { x: 8, y: 0 }.shift(-1, 0, 9).is_none());
+    }
+
+    #[test]
+    fn edge_of_small_board() {
+        // X X X [X] | on 9x9, the edge is a wall
+        let four = |size| {
+            let mut board = Board::new(size);
+            for x in 5..8 {
+                board.put(Move { x, y: 0 }, Stone::Black);
+            }
+            Line::new(&board, Move { x: 8, y: 0 }, Stone::Black, Direction::Horizontal)
+                .four_points(true)
+        };
+        assert_eq!(four(9), vec![Move { x: 4, y: 0 }]);
+        assert_eq!(four(15), vec![Move { x: 4, y: 0 }, Move { x: 9, y: 0 }]);
+    }
+
+    #[test]
+    fn win_on_large_board() {
+        let mut board = Board::new(19);
+        for x in 14..18 {
+            board.put(Move { x, y: 18 }, Stone::Black);
+        }
+        let mv = Move { x: 18, y: 18 };
+        assert!(FreestyleRule.is_winning(&board, mv, Player::Black));
+        assert!(matches!(FreestyleRule.put(&mut board, mv, Player::Black), Ok(PutOutcome::Win)));
+    }
+
+    #[test]
+    fn draw_on_small_board() {
+        let mut board = almost_full(9);
+        let turn = board.turn();
+        assert!(matches!(
+            FreestyleRule.put(&mut board, Move { x: 8, y: 8 }, turn),
+            Ok(PutOutcome::Draw)
+        ));
+    }
+
+    #[test]
+    fn bot_stays_on_board() {
+        for size in [9, 19] {
+            let mut board = Board::new(size);
+            let mut model = omok_model();
+            let first = model.next_move(&board, board.center(), SearchLimits::depth(2)).unwrap();
+            assert_eq!(first, board.center());
+
+            // stones in the corner, candidates must not leave the board
+            let corner = Move { x: size - 1, y: size - 1 };
+            board.put(corner, Stone::Black);
+            let next = model.next_move(&board, corner, SearchLimits::depth(2)).unwrap();
+            assert!(next.x < size && next.y < size);
+        }
+    }
+}
+
+mod history {
+    use crate::core::board::{Board, Move, Stone, Turn};
+    use crate::core::game::Game;
+    use crate::core::rule::{PutOutcome, FreestyleRule};
+
+    fn moves(game: &Game) -> Vec<Move> {
+        game.history().iter().map(|&(mv, _)| mv).collect()
+    }
+
+    #[test]
+    fn undo_redo() {
+        let mut game = Game::new(15);
+        let a = Move { x: 7, y: 7 };
+        let b = Move { x: 8, y: 8 };
+        game.put(&FreestyleRule, a).unwrap();
+        game.put(&FreestyleRule, b).unwrap();
+        let after = *game.board();
+
+        assert_eq!(game.undo(), Some(b));
+        assert_eq!(game.board().get(b), Stone::None);
+        assert_eq!(game.board().turn(), Turn::White);
+        assert_eq!(game.undo(), Some(a));
+        assert_eq!(*game.board(), Board::blank());
+        assert_eq!(game.undo(), None);
+
+        assert_eq!(game.redo(), Some(a));
+        assert_eq!(game.redo(), Some(b));
+        assert_eq!(game.redo(), None);
+        assert_eq!(*game.board(), after);
+        assert_eq!(moves(&game), vec![a, b]);
+    }
+
+    #[test]
+    fn put_discards_redo() {
+        let mut game = Game::new(15);
+        game.put(&FreestyleRule, Move { x: 7, y: 7 }).unwrap();
+        game.put(&FreestyleRule, Move { x: 8, y: 8 }).unwrap();
+        game.undo();
+
+        let c = Move { x: 6, y: 6 };
+        game.put(&FreestyleRule, c).unwrap();
+        assert!(!game.can_redo());
+        assert_eq!(game.last_move(), Some(c));
+        assert_eq!(game.board().get(Move { x: 8, y: 8 }), Stone::None);
+    }
+
+    #[test]
+    fn goto_keeps_outcome() {
+        let mut game = Game::new(15);
+        for i in 0..4 {
+            game.put(&FreestyleRule, Move { x: i, y: 0 }).unwrap();
+            game.put(&FreestyleRule, Move { x: i, y: 1 }).unwrap();
+        }
+        assert_eq!(game.put(&FreestyleRule, Move { x: 4, y: 0 }).unwrap(), PutOutcome::Win);
+
+        game.goto(3);
+        assert_eq!(game.board().ply(), 3);
+        assert_eq!(game.outcome(), PutOutcome::Continue);
+
+        game.goto(9);
+        assert_eq!(game.outcome(), PutOutcome::Win);
+        game.goto(0);
+        assert_eq!(game.last_move(), None);
+    }
+}
+
+mod bitboard {
+    use crate::core::board::{Board, Move, Stone};
+    use crate::core::pattern::DIRECTION;
+
+    /// line bits agree with the cells walked one by one
+    #[test]
+    fn line_bits() {
+        for size in [9, 15, 19] {
+            let mut board = Board::new(size);
+            for i in 0..size * size {
+                let stone = match i % 7 {
+                    0 | 3 => Stone::Black,
+                    1 | 5 => Stone::White,
+                    _ => continue,
+                };
+                board.put(Move { x: i % size, y: i / size }, stone);
+            }
+
+            for y in 0..size {
+                for x in 0..size {
+                    let mv = Move { x, y };
+                    for direction in DIRECTION {
+                        let bits = board.line_bits(mv, direction);
+                        let (dx, dy) = direction.delta();
+                        for offset in -20..20i32 {
+                            let bit = bits.pos as i32 + offset;
+                            if !(0..32).contains(&bit) {
+                                continue;
+                            }
+                            let bit = 1 << bit;
+                            match mv.shift(-dx * offset, -dy * offset, size) {
+                                Some(p) => {
+                                    assert!(bits.mask & bit != 0);
+                                    assert_eq!(bits.black & bit != 0, board.get(p) == Stone::Black);
+                                    assert_eq!(bits.white & bit != 0, board.get(p) == Stone::White);
+                                },
+                                None => assert!(bits.mask & bit == 0),
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn undo_clears_bits() {
+        let mut board = Board::blank();
+        let mv = Move { x: 3, y: 11 };
+        board.put(mv, Stone::White);
+        board.undo_unchecked(mv);
+        assert_eq!(board, Board::blank());
+    }
+}
+
+mod tt {
+    use crate::bot::eval::{Eval, PatternEval};
+    use crate::bot::model::SearchLimits;
+    use crate::bot::prune::{NeighborPrune, Prune};
+    use crate::bot::tt::{Bound, TT, TTEntry};
+    use crate::core::board::{Board, Move};
+    use crate::core::rule::{Rule, PutOutcome, OmokRule};
+    use super::{omok_model, played};
+
+    #[test]
+    fn entry() {
+        let tt = TT::with_mb(1);
+        let best = Some(Move { x: 3, y: 4 });
+        tt.put(TTEntry { hash: 42, value: 1.5, depth: 3, bound: Bound::Lower, best });
+        let entry = tt.get(42).unwrap();
+        assert_eq!((entry.bound, entry.best), (Bound::Lower, best));
+        assert!(tt.get(42 + tt.capacity() as u64).is_none());
+
+        let stats = tt.stats();
+        assert_eq!((stats.probes, stats.hits, stats.stores), (2, 1, 1));
+    }
+
+    fn put(tt: &TT, hash: u64, depth: u32) {
+        tt.put(TTEntry { hash, value: 0.0, depth, bound: Bound::Exact, best: None });
+    }
+
+    /// entries written by threads at once are never mixed up
+    #[test]
+    fn concurrent() {
+        use std::thread;
+
+        let tt = TT::with_mb(0);
+        thread::scope(|scope| {
+            for t in 0..4u32 {
+                let tt = &tt;
+                scope.spawn(move || {
+                    for i in 0..10000u64 {
+                        // the value tells the hash it's stored for
+                        let hash = i % 8;
+                        let best = Some(Move { x: t as usize, y: hash as usize });
+                        tt.put(TTEntry { hash, value: hash as f32, depth: t, bound: Bound::Exact, best });
+                        if let Some(entry) = tt.get(hash) {
+                            assert_eq!(entry.value, hash as f32);
+                            assert_eq!(entry.best.unwrap().y, hash as usize);
+                        }
+                    }
+                });
+            }
+        });
+    }
+
+    #[test]
+    fn replace_shallowest() {
+        // a single bucket
+        let tt = TT::with_mb(0);
+        for hash in 1..=4 {
+            put(&tt, hash, hash as u32);
+        }
+        assert_eq!(tt.stats().collisions, 0);
+
+        put(&tt, 5, 3);
+        assert!(tt.get(1).is_none());
+        assert!((2..=5).all(|hash| tt.get(hash).is_some()));
+        assert_eq!(tt.stats().collisions, 1);
+    }
+
+    #[test]
+    fn replace_old() {
+        let tt = TT::with_mb(0);
+        put(&tt, 1, 5);
+        put(&tt, 1, 2);
+        assert_eq!(tt.get(1).unwrap().depth, 5);
+
+        // deep entries of the previous searches are aged out
+        tt.new_generation();
+        put(&tt, 1, 2);
+        assert_eq!(tt.get(1).unwrap().depth, 2);
+
+        for hash in 2..=4 {
+            put(&tt, hash, 4);
+        }
+        tt.new_generation();
+        tt.new_generation();
+        put(&tt, 5, 1);
+        put(&tt, 6, 1);
+        assert!(tt.get(5).is_some() && tt.get(6).is_some());
+        assert_eq!(tt.usage(), 500);
+    }
+
+    const DEPTH: u32 = 5;
+
+    /// plain negamax without pruning or transposition table
+    fn minimax(board: &mut Board, d: u32, mv: Move) -> f32 {
+        let eval = PatternEval { rule: OmokRule };
+        if d == 0 {
+            return eval.eval(board, mv);
+        }
+        let possible = NeighborPrune.possible(board, mv);
+        if possible.is_empty() {
+            return eval.eval(board, mv);
+        }
+
+        let mut max = f32::NEG_INFINITY;
+        for mv in possible {
+            let turn = board.turn();
+            let value = match OmokRule.put(board, mv, turn) {
+                Ok(outcome) => {
+                    let value = match outcome {
+                        PutOutcome::Continue => -minimax(board, d - 1, mv),
+                        PutOutcome::Win => 100000.0 - (DEPTH - d + 1) as f32,
+                        PutOutcome::Draw => 0.0,
+                    };
+                    board.undo_unchecked(mv);
+                    value
+                },
+                Err(_) => f32::NEG_INFINITY,
+            };
+            max = max.max(value);
+        }
+        max
+    }
+
+    /// bounds stored by alpha-beta must not change the root value
+    #[test]
+    fn same_value_as_minimax() {
+        // small positions on the 9x9 board, deep enough to have transpositions
+        let positions: [&[(usize, usize)]; 3] = [
+            &[(0, 0), (1, 1)],
+            &[(8, 0), (7, 1), (8, 1)],
+            &[(0, 8), (1, 7), (0, 7), (1, 8)],
+        ];
+        for moves in positions {
+            let (mut board, last) = played(9, moves);
+
+            let mut model = omok_model();
+            let result = model.search(&board, last, SearchLimits::depth(DEPTH));
+            assert_eq!(result.score, minimax(&mut board, DEPTH, last));
+        }
+    }
+
+    /// board size, search depth and moves from black
+    type Position = (usize, u32, &'static [(usize, usize)]);
+
+    /// the table only saves work, the result is the same without it
+    #[test]
+    fn same_move_without_tt() {
+        let positions: [Position; 5] = [
+            (9, 4, &[(4, 4), (5, 5)]),
+            (9, 4, &[(4, 4), (3, 5), (5, 4), (3, 3)]),
+            (15, 3, &[(7, 7), (8, 8), (7, 8), (7, 9), (6, 7)]),
+            // black wins from the open three
+            (15, 3, &[(7, 7), (8, 6), (7, 8), (9, 6), (7, 9), (2, 2)]),
+            // white has to block the open three
+            (15, 3, &[(5, 5), (9, 9), (6, 6), (9, 10), (7, 7)]),
+        ];
+        for (size, depth, moves) in positions {
+            let (board, last) = played(size, moves);
+            let search = |use_tt| {
+                let mut model = omok_model();
+                model.use_tt = use_tt;
+                model.search(&board, last, SearchLimits::depth(depth))
+            };
+            let with = search(true);
+            let without = search(false);
+            assert_eq!((with.best, with.score), (without.best, without.score));
+            assert!(with.stats.nodes <= without.stats.nodes);
+            assert_eq!(without.stats.tt_hits, 0);
+        }
+    }
+}
+
+mod bench {
+    use crate::bot::model::SearchLimits;
+    use crate::bot::{eval, prune};
+    use crate::core::board::Board;
+    use crate::core::rule::{Rule, OmokRule};
+    use super::{omok_model, played};
+
+    /// nodes per second of the depth 5 search,
+    /// run with `cargo test --release -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn negamax_nps() {
+        let (board, last) = played(15, &[(7, 7), (8, 8), (6, 8), (8, 6), (7, 9), (6, 7), (9, 7), (7, 6)]);
+
+        // plain alpha-beta, then with pvs and aspiration windows, then on 4 threads
+        for (enabled, threads) in [(false, 1), (true, 1), (true, 4)] {
+            let mut model = omok_model();
+            model.pvs = enabled;
+            model.aspiration = enabled;
+            model.threads = threads;
+            let result = model.search(&board, last, SearchLimits::depth(5));
+
+            let nodes = result.stats.nodes;
+            println!(
+                "pvs: {}, threads: {}, nodes: {}, elapsed: {:?}, nps: {:.0}, branching factor: {:.2}, researches: {}",
+                enabled, threads, nodes, result.elapsed, nodes as f64 / result.elapsed.as_secs_f64(),
+                result.stats.branching_factor(), result.stats.researches,
+            );
+        }
+    }
+
+    /// games of mcts against negamax with the same time, each taking both colors,
+    /// run with `cargo test --release -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn mcts_vs_negamax() {
+        use crate::bot::mcts::MctsModel;
+        use crate::bot::model::Model;
+        use crate::core::board::Player;
+        use crate::core::rule::PutOutcome;
+        use std::time::Duration;
+
+        let limits = || SearchLimits {
+            depth: 8,
+            ..SearchLimits::move_time(Duration::from_millis(500))
+        };
+        for mcts_black in [true, false] {
+            let mut mcts = MctsModel::new(eval::PatternEval { rule: OmokRule }, prune::NeighborPrune, OmokRule);
+            mcts.playouts = u32::MAX;
+            let mut negamax = omok_model();
+            let mut board = Board::blank();
+            let mut mv = board.center();
+            OmokRule.put(&mut board, mv, Player::Black).unwrap();
+
+            let result = loop {
+                let turn = board.turn();
+                let model: &mut dyn Model = if (turn == Player::Black) == mcts_black {
+                    &mut mcts
+                } else {
+                    &mut negamax
+                };
+                let Some(next) = model.next_move(&board, mv, limits()) else {
+                    break format!("{:?} resigned", turn);
+                };
+                mv = next;
+                match OmokRule.put(&mut board, mv, turn) {
+                    Ok(PutOutcome::Continue) => (),
+                    Ok(PutOutcome::Win) => break format!("{:?} won", turn),
+                    Ok(PutOutcome::Draw) => break "draw".to_string(),
+                    Err(e) => break format!("{:?} played {:?}: {:?}", turn, mv, e),
+                }
+            };
+            println!("mcts black: {}, {} at ply {}", mcts_black, result, board.ply());
+        }
+    }
+}
+
+mod zobrist {
+    use crate::core::board::{Board, Move, Stone};
+    use crate::core::zobrist;
+    use super::played;
+
+    #[test]
+    fn transposition() {
+        let (a, _) = played(15, &[(7, 7), (8, 8), (6, 6)]);
+        let (b, _) = played(15, &[(6, 6), (8, 8), (7, 7)]);
+        assert_eq!(a.hash(), b.hash());
+
+        // a move toggles the side to move, so the same stones
+        // with the other side to move differ by the side key
+        let (c, _) = played(15, &[(7, 7), (8, 8), (6, 6), (9, 9)]);
+        assert_eq!(c.hash(), a.hash() ^ zobrist::stone(Move { x: 9, y: 9 }, 1) ^ zobrist::side());
+        assert_ne!(zobrist::side(), 0);
+        assert_ne!(Board::blank().hash(), Board::new(19).hash());
+    }
+
+    #[test]
+    fn undo_restores() {
+        let (mut board, _) = played(15, &[(7, 7)]);
+        let hash = board.hash();
+        let mv = Move { x: 3, y: 11 };
+        board.put(mv, Stone::White);
+        assert_ne!(board.hash(), hash);
+        board.undo_unchecked(mv);
+        assert_eq!(board.hash(), hash);
+    }
+
+    /// keys don't depend on the run, hashes can be stored
+    #[test]
+    fn reproducible() {
+        let (board, _) = played(15, &[(7, 7), (8, 8)]);
+        assert_eq!(board.hash(), 15009566108013353533);
+    }
+}
+
+mod vcf {
+    use std::time::Instant;
+    use crate::bot::model::{SearchLimits, StopToken};
+    use crate::bot::vcf::Vcf;
+    use crate::core::board::{Board, Move, Player, Stone};
+    use crate::core::rule::{CheckResult, PutOutcome, Rule, OmokRule, RenjuRule};
+    use super::{board_with, omok_model};
+
+    /// points where the player makes five
+    fn fives(rule: &impl Rule, board: &Board, player: Player) -> Vec<Move> {
+        (0..board.size())
+            .flat_map(|y| (0..board.size()).map(move |x| Move { x, y }))
+            .filter(|&p| board.get(p) == Stone::None)
+            .filter(|&p| matches!(rule.check(board, p, player), CheckResult::Win))
+            .collect()
+    }
+
+    /// every defender move is forced, and the attacker wins at the end
+    fn assert_wins(rule: &impl Rule, board: &Board, line: &[Move]) {
+        let attacker = board.turn();
+        let mut board = *board;
+        let mut outcome = PutOutcome::Continue;
+        for (i, &mv) in line.iter().enumerate() {
+            if i % 2 == 1 {
+                // the defender can't win and blocks the only five point
+                assert!(fives(rule, &board, attacker.next()).is_empty());
+                assert_eq!(fives(rule, &board, attacker), vec![mv]);
+            }
+            let turn = board.turn();
+            outcome = rule.put(&mut board, mv, turn).unwrap();
+        }
+        if outcome != PutOutcome::Win {
+            // a four which can't be blocked
+            assert!(fives(rule, &board, attacker.next()).is_empty());
+            assert!(fives(rule, &board, attacker).len() >= 2);
+        }
+    }
+
+    /// black closed threes on rows 6 and 7, and a vertical two on x = 6
+    pub(super) fn chain() -> Board {
+        board_with(
+            &[(3, 7), (4, 7), (5, 7), (3, 6), (4, 6), (5, 6), (6, 8), (6, 9)],
+            &[(2, 7), (2, 6), (6, 10), (0, 14), (2, 14), (4, 14), (14, 0), (14, 2)],
+        )
+    }
+
+    #[test]
+    fn double_four() {
+        let board = board_with(
+            &[(3, 7), (4, 7), (5, 7), (6, 4), (6, 5), (6, 6)],
+            &[(2, 7), (6, 3), (0, 14), (2, 14), (14, 0), (14, 2)],
+        );
+        let line = Vcf::new(&OmokRule).solve(&board).unwrap();
+        assert_wins(&OmokRule, &board, &line);
+
+        // 4-4 is forbidden for black
+        assert_eq!(Vcf::new(&RenjuRule).solve(&board), None);
+    }
+
+    #[test]
+    fn continuous_fours() {
+        let board = chain();
+        let line = Vcf::new(&OmokRule).solve(&board).unwrap();
+        assert!(line.len() >= 3, "{:?}", line);
+        assert_wins(&OmokRule, &board, &line);
+    }
+
+    #[test]
+    fn defender_four() {
+        // white open four on row 3, black has no five
+        let board = board_with(
+            &[(3, 7), (4, 7), (5, 7), (3, 6), (4, 6), (5, 6), (6, 8), (6, 9)],
+            &[(2, 7), (2, 6), (6, 10), (10, 3), (11, 3), (12, 3), (13, 3), (14, 14)],
+        );
+        assert_eq!(Vcf::new(&OmokRule).solve(&board), None);
+
+        // no four at all
+        assert_eq!(Vcf::new(&OmokRule).solve(&Board::blank()), None);
+    }
+
+    #[test]
+    fn stopped() {
+        let stop = StopToken::new();
+        stop.stop();
+        let mut vcf = Vcf::new(&OmokRule);
+        vcf.budget = vcf.budget.until(None, stop);
+        assert_eq!(vcf.solve(&chain()), None);
+
+        vcf.budget = vcf.budget.until(Some(Instant::now()), StopToken::new());
+        assert_eq!(vcf.solve(&chain()), None);
+    }
+
+    /// the win by fours is found beyond the depth of the search
+    #[test]
+    fn negamax_uses_vcf() {
+        let board = chain();
+        let mut model = omok_model();
+        let result = model.search(&board, Move { x: 14, y: 2 }, SearchLimits::depth(1));
+        assert!(result.score > 90000.0);
+
+        let mut next = board;
+        OmokRule.put(&mut next, result.best.unwrap(), board.turn()).unwrap();
+        assert!(!fives(&OmokRule, &next, next.turn().next()).is_empty());
+    }
+}
+
+mod vct {
+    use std::time::Instant;
+    use crate::bot::eval::PatternEval;
+    use crate::bot::model::{NegamaxModel, SearchLimits, StopToken};
+    use crate::bot::prune::NeighborPrune;
+    use crate::bot::vcf::{Budget, Vcf};
+    use crate::bot::vct::Vct;
+    use crate::core::board::{Board, Move, Player, Stone};
+    use crate::core::pattern::{Line, Pattern};
+    use crate::core::rule::{CheckResult, FreestyleRule, PutOutcome, Rule, RenjuRule};
+    use super::board_with;
+
+    fn empty_cells(board: &Board) -> impl Iterator<Item = Move> + '_ {
+        (0..board.size())
+            .flat_map(|y| (0..board.size()).map(move |x| Move { x, y }))
+            .filter(|&p| board.get(p) == Stone::None)
+    }
+
+    fn fives(rule: &impl Rule, board: &Board, player: Player) -> usize {
+        empty_cells(board)
+            .filter(|&p| matches!(rule.check(board, p, player), CheckResult::Win))
+            .count()
+    }
+
+    fn straight_fours(rule: &impl Rule, board: &Board, player: Player) -> usize {
+        empty_cells(board)
+            .filter(|&p| {
+                Line::all(board, p, player.to_stone())
+                    .iter()
+                    .any(|line| line.classify(false) == Pattern::OpenFour)
+                    && rule.is_valid(board, p, player)
+            })
+            .count()
+    }
+
+    /// the moves are legal, and the attacker wins or has threats at the end
+    fn assert_wins(rule: &impl Rule, board: &Board, line: &[Move]) {
+        let attacker = board.turn();
+        let mut board = *board;
+        let mut outcome = PutOutcome::Continue;
+        for &mv in line {
+            let turn = board.turn();
+            outcome = rule.put(&mut board, mv, turn).unwrap();
+        }
+        if outcome != PutOutcome::Win {
+            assert_eq!(board.turn(), attacker.next());
+            assert_eq!(fives(rule, &board, attacker.next()), 0);
+            assert!(fives(rule, &board, attacker) >= 2 || straight_fours(rule, &board, attacker) >= 2);
+        }
+    }
+
+    /// crossing twos of black, (7, 7) makes two open threes
+    const CROSS: [(usize, usize); 4] = [(5, 7), (6, 7), (7, 5), (7, 6)];
+    const CORNERS: [(usize, usize); 4] = [(0, 14), (2, 14), (14, 0), (14, 2)];
+
+    #[test]
+    fn double_three() {
+        let board = board_with(&CROSS, &CORNERS);
+        assert_eq!(Vcf::new(&FreestyleRule).solve(&board), None);
+
+        let line = Vct::new(&FreestyleRule).solve(&board).unwrap();
+        assert_eq!(line.first(), Some(&Move { x: 7, y: 7 }));
+        assert_wins(&FreestyleRule, &board, &line);
+    }
+
+    #[test]
+    fn renju_forbidden() {
+        // 3-3 is forbidden for black, not for white
+        let board = board_with(&CROSS, &CORNERS);
+        let vct = Vct { budget: Budget::nodes(2000), ..Vct::new(&RenjuRule) };
+        if let Some(line) = vct.solve(&board) {
+            assert_ne!(line.first(), Some(&Move { x: 7, y: 7 }));
+            assert_wins(&RenjuRule, &board, &line);
+        }
+
+        let board = board_with(&[(0, 14), (2, 14), (14, 0), (14, 2), (14, 14)], &CROSS);
+        assert_eq!(board.turn(), Player::White);
+        let line = Vct::new(&RenjuRule).solve(&board).unwrap();
+        assert_wins(&RenjuRule, &board, &line);
+    }
+
+    #[test]
+    fn counter_four() {
+        // white open four, black can't win first
+        let mut white = CORNERS.to_vec();
+        white.extend([(10, 3), (11, 3), (12, 3), (13, 3)]);
+        let mut black = CROSS.to_vec();
+        black.extend([(0, 0), (0, 2), (0, 4), (0, 6)]);
+        let board = board_with(&black, &white);
+        assert_eq!(Vct::new(&FreestyleRule).solve(&board), None);
+
+        // white closed four, black has to block it
+        let mut white = vec![(10, 3), (11, 3), (12, 3), (13, 3), (0, 14), (2, 14), (0, 0)];
+        let mut black = CROSS.to_vec();
+        black.extend([(9, 3), (14, 5), (12, 12)]);
+        let board = board_with(&black, &white);
+        assert_eq!(Vct::new(&FreestyleRule).solve(&board), None);
+
+        // the block makes an open three, then (7, 7) wins
+        black.pop();
+        black.push((14, 4));
+        white.pop();
+        white.push((0, 2));
+        let board = board_with(&black, &white);
+        let line = Vct::new(&FreestyleRule).solve(&board).unwrap();
+        assert_eq!(line.first(), Some(&Move { x: 14, y: 3 }));
+        assert_wins(&FreestyleRule, &board, &line);
+
+        assert_eq!(Vct::new(&FreestyleRule).solve(&Board::blank()), None);
+    }
+
+    #[test]
+    fn negamax_uses_vct() {
+        let board = board_with(&CROSS, &CORNERS);
+        let mut model = NegamaxModel::new(
+            PatternEval { rule: FreestyleRule }, NeighborPrune, FreestyleRule
+        );
+        model.vct = true;
+        let result = model.search(&board, Move { x: 14, y: 2 }, SearchLimits::depth(1));
+        assert_eq!(result.best, Some(Move { x: 7, y: 7 }));
+        assert!(result.score > 90000.0);
+    }
+
+    /// moves which lose to the threats of the opponent are not searched
+    #[test]
+    fn negamax_defends_vct() {
+        // white open three, only the ends next to it stop the straight four
+        let board = board_with(&[(0, 0), (14, 0), (0, 14)], &[(5, 7), (6, 7), (7, 7)]);
+        assert_eq!(board.turn(), Player::Black);
+
+        let mut model = NegamaxModel::new(
+            PatternEval { rule: FreestyleRule }, NeighborPrune, FreestyleRule
+        );
+        model.vct = true;
+        let result = model.search(&board, Move { x: 7, y: 7 }, SearchLimits::depth(1));
+        assert!([Move { x: 4, y: 7 }, Move { x: 8, y: 7 }].contains(&result.best.unwrap()));
+        let mut next = board;
+        FreestyleRule.put(&mut next, result.best.unwrap(), Player::Black).unwrap();
+        assert_eq!(Vct::new(&FreestyleRule).solve(&next), None);
+    }
+
+    /// the solves before the search give up with the search
+    #[test]
+    fn stopped() {
+        let board = board_with(&CROSS, &CORNERS);
+        let stop = StopToken::new();
+        stop.stop();
+        let mut vct = Vct::new(&FreestyleRule);
+        vct.budget = vct.budget.until(None, stop);
+        assert_eq!(vct.solve(&board), None);
+
+        vct.budget = vct.budget.until(Some(Instant::now()), StopToken::new());
+        assert_eq!(vct.solve(&board), None);
+    }
+}
+
+mod pns {
+    use crate::bot::model::{Model, Proof, ProofNumberModel, SearchLimits};
+    use crate::bot::prune::NeighborPrune;
+    use crate::core::board::{Move, Stone};
+    use crate::core::rule::{OmokRule, Rule};
+    use super::board_with;
+    use super::vcf::chain;
+
+    #[test]
+    fn five() {
+        let board = board_with(
+            &[(3, 7), (4, 7), (5, 7), (6, 7)],
+            &[(2, 7), (0, 14), (2, 14), (4, 14)],
+        );
+        let model = ProofNumberModel::new(NeighborPrune, OmokRule);
+        let result = model.prove(&board, Move { x: 4, y: 14 }, &SearchLimits::depth(1));
+        assert_eq!(result.proof, Proof::Win);
+        assert_eq!(result.best, Some(Move { x: 7, y: 7 }));
+        // the position and the five
+        assert_eq!(result.tree_size, 2);
+    }
+
+    #[test]
+    fn continuous_fours() {
+        let board = chain();
+        let mut model = ProofNumberModel::new(NeighborPrune, OmokRule);
+        let result = model.prove(&board, Move { x: 14, y: 2 }, &SearchLimits::depth(1));
+        assert_eq!(result.proof, Proof::Win);
+        assert!(result.tree_size > 2);
+
+        // the winning move makes a four
+        let best = model.next_move(&board, Move { x: 14, y: 2 }, SearchLimits::depth(1)).unwrap();
+        assert_eq!(Some(best), result.best);
+        let mut next = board;
+        OmokRule.put(&mut next, best, board.turn()).unwrap();
+        let fours = (0..15)
+            .flat_map(|y| (0..15).map(move |x| Move { x, y }))
+            .filter(|&p| next.get(p) == Stone::None)
+            .filter(|&p| OmokRule.is_winning(&next, p, board.turn()))
+            .count();
+        assert!(fours >= 1);
+    }
+
+    #[test]
+    fn open_four() {
+        // white open four, black can't block both ends
+        let board = board_with(
+            &[(0, 0), (0, 2), (0, 4), (0, 6)],
+            &[(5, 7), (6, 7), (7, 7), (8, 7)],
+        );
+        let model = ProofNumberModel::new(NeighborPrune, OmokRule);
+        let result = model.prove(&board, Move { x: 8, y: 7 }, &SearchLimits::depth(1));
+        assert_eq!(result.proof, Proof::Loss);
+        assert!(result.tree_size > 2);
+    }
+
+    #[test]
+    fn budget() {
+        let board = board_with(&[(7, 7)], &[(8, 8)]);
+        let mut model = ProofNumberModel::new(NeighborPrune, OmokRule);
+        model.max_nodes = 200;
+        let result = model.prove(&board, Move { x: 8, y: 8 }, &SearchLimits::depth(1));
+        assert_eq!(result.proof, Proof::Unknown);
+        assert_eq!(result.tree_size, 0);
+        assert!(result.best.is_some());
+        assert!(result.nodes <= 201);
+    }
+}
+
+mod mcts {
+    use crate::bot::eval::PatternEval;
+    use crate::bot::mcts::MctsModel;
+    use crate::bot::model::{Model, SearchLimits};
+    use crate::bot::prune::NeighborPrune;
+    use crate::core::board::{Move, Stone};
+    use crate::core::rule::OmokRule;
+    use super::board_with;
+
+    fn model(playouts: u32) -> MctsModel<PatternEval<OmokRule>, NeighborPrune, OmokRule> {
+        let mut model = MctsModel::new(PatternEval { rule: OmokRule }, NeighborPrune, OmokRule);
+        model.playouts = playouts;
+        model.rollout_depth = 4;
+        model
+    }
+
+    #[test]
+    fn five() {
+        let board = board_with(
+            &[(3, 7), (4, 7), (5, 7), (6, 7)],
+            &[(2, 7), (0, 14), (2, 14), (4, 14)],
+        );
+        let mut model = model(300);
+        let result = model.search(&board, Move { x: 4, y: 14 }, SearchLimits::depth(1));
+        assert_eq!(result.best, Some(Move { x: 7, y: 7 }));
+        assert!(result.score > 90.0);
+        assert_eq!(result.stats.nodes, 300);
+    }
+
+    #[test]
+    fn block_four() {
+        // white four on row 7, black has to block at (4, 7)
+        let board = board_with(
+            &[(0, 0), (0, 2), (0, 4), (9, 9), (9, 7)],
+            &[(5, 7), (6, 7), (7, 7), (8, 7), (0, 14)],
+        );
+        let mut model = model(300);
+        let best = model.next_move(&board, Move { x: 0, y: 14 }, SearchLimits::depth(1));
+        assert_eq!(best, Some(Move { x: 4, y: 7 }));
+    }
+
+    #[test]
+    fn tree_reuse() {
+        let board = board_with(&[(7, 7), (6, 6)], &[(8, 8), (8, 6)]);
+        let mut model = model(400);
+        let result = model.search(&board, Move { x: 8, y: 6 }, SearchLimits::depth(1));
+        assert_eq!(result.stats.nodes, 400);
+
+        // the own move and the reply the tree searched the most
+        let mut next = board;
+        let best = result.best.unwrap();
+        next.put(best, Stone::Black);
+        let reply = model.search(&next, best, SearchLimits::depth(1)).best.unwrap();
+        next.put(reply, Stone::White);
+
+        // the kept playouts count to the budget
+        let result = model.search(&next, reply, SearchLimits::depth(1));
+        assert!(result.stats.nodes < 400);
+        assert!(result.best.is_some());
+    }
+
+    #[test]
+    fn same_seed() {
+        let board = board_with(&[(7, 7)], &[(8, 8)]);
+        let moves: Vec<_> = (0..2)
+            .map(|_| model(100).next_move(&board, Move { x: 8, y: 8 }, SearchLimits::depth(1)))
+            .collect();
+        assert_eq!(moves[0], moves[1]);
+    }
 }
\ No newline at end of file
@@ -1,18 +1,29 @@
 use std::sync::{Arc, mpsc, RwLock, Mutex};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use eframe::egui;
-use crate::core::board::{Board, Move, Player, Stone, Turn};
-use crate::core::rule::{self, Rule, PutOutcome, PutError};
-use crate::bot::model::{self, Model};
+use crate::core::board::{Board, Move, Player, Stone, Turn, DEFAULT_SIZE};
+use crate::core::game::Game;
+use crate::core::rule::{self, Rule, PutOutcome, PutError, Forbidden, ForbiddenKind};
+use crate::core::opening::{Choice, Opening, OpeningRule, ProRule, Request, Side};
+use crate::bot::model::{self, Clock, Model, SearchInfo, SearchLimits, StopToken};
 use crate::bot::eval;
+use crate::bot::mcts;
 use crate::bot::prune;
+use crate::bot::vct::Vct;
 
 
 // The app is consisted of independent games.
 
 pub struct MyApp {
     mode: AppMode,
+    rule: RuleKind,
+    opening: OpeningRule,
+    /// dimension of the board
+    size: usize,
+    /// models of the first and the second player in Bot vs Bot,
+    /// the bot in Bot mode is the second
+    bots: [BotKind; 2],
     games: GameGroup,
     ui_setting: UiSetting,
     input_manager: Arc<RwLock<InputManager>>,
@@ -23,69 +34,142 @@ enum AppMode {
     Pvp, Bot, TwoBot, About,
 }
 
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum RuleKind {
+    Omok, Renju, Freestyle, StandardGomoku, Caro,
+}
+
+impl RuleKind {
+    const ALL: [RuleKind; 5] = [
+        RuleKind::Omok, RuleKind::Renju,
+        RuleKind::Freestyle, RuleKind::StandardGomoku, RuleKind::Caro,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            RuleKind::Omok => "Omok",
+            RuleKind::Renju => "Renju",
+            RuleKind::Freestyle => "Freestyle",
+            RuleKind::StandardGomoku => "Standard Gomoku",
+            RuleKind::Caro => "Caro",
+        }
+    }
+
+    fn games(
+        &self, opening: OpeningRule, size: usize, bots: [BotKind; 2],
+        input_manager: &Arc<RwLock<InputManager>>,
+    ) -> GameGroup {
+        match self {
+            RuleKind::Omok => GameGroup::new(rule::OmokRule, opening, size, bots, input_manager),
+            RuleKind::Renju => GameGroup::new(rule::RenjuRule, opening, size, bots, input_manager),
+            RuleKind::Freestyle => GameGroup::new(rule::FreestyleRule, opening, size, bots, input_manager),
+            RuleKind::StandardGomoku => GameGroup::new(rule::StandardGomokuRule, opening, size, bots, input_manager),
+            RuleKind::Caro => GameGroup::new(rule::CaroRule, opening, size, bots, input_manager),
+        }
+    }
+}
+
+/// search algorithm of a bot
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum BotKind {
+    Negamax, Mcts,
+}
+
+impl BotKind {
+    const ALL: [BotKind; 2] = [BotKind::Negamax, BotKind::Mcts];
+
+    fn name(&self) -> &'static str {
+        match self {
+            BotKind::Negamax => "Negamax",
+            BotKind::Mcts => "MCTS",
+        }
+    }
+
+    fn player<R: Rule + Copy + 'static>(&self, rule: R) -> BotPlayer {
+        match self {
+            BotKind::Negamax => BotPlayer::new(bot_model(rule), bot_limits()),
+            BotKind::Mcts => BotPlayer::new(mcts_model(rule), bot_limits()),
+        }
+    }
+}
+
+// selectable board dimensions
+const BOARD_SIZES: [usize; 3] = [9, 15, 19];
+
+// time of each player for the whole game
+const GAME_CLOCK: Clock = Clock {
+    remaining: Duration::from_secs(300),
+    increment: Duration::from_secs(3),
+};
+
+/// thinking time of the bots (the game clock can make it shorter)
+fn bot_limits() -> SearchLimits {
+    SearchLimits {
+        depth: 8,
+        ..SearchLimits::move_time(Duration::from_secs(2))
+    }
+}
+
+/// bot searching on a single thread (the default),
+/// lazy smp would make the game differ from the replay of the seed
+fn bot_model<R: Rule + Copy>(rule: R) -> model::NegamaxModel<eval::PatternEval<R>, prune::NeighborPrune, R> {
+    model::NegamaxModel::with_seed(
+        eval::PatternEval { rule },
+        prune::NeighborPrune,
+        rule,
+        // logged by the model to replay the game
+        rand::random(),
+    )
+}
+
+/// bot playing out until the time of `bot_limits`
+fn mcts_model<R: Rule + Copy>(rule: R) -> mcts::MctsModel<eval::PatternEval<R>, prune::NeighborPrune, R> {
+    let mut model = mcts::MctsModel::with_seed(
+        eval::PatternEval { rule },
+        prune::NeighborPrune,
+        rule,
+        // logged, though the playouts stop on time so a replay can differ
+        rand::random(),
+    );
+    model.playouts = u32::MAX;
+    model
+}
+
 impl Default for MyApp {
     fn default() -> Self {
-        let rule = rule::OmokRule;
+        let rule = RuleKind::Omok;
+        let opening = OpeningRule::Free;
+        let size = DEFAULT_SIZE;
+        let bots = [BotKind::Negamax; 2];
         let input_manager = Arc::new(RwLock::new(InputManager::default()));
 
         let mut result = Self {
             mode: AppMode::Bot,     // default mode
-            games: GameGroup {
-                pvp: GameData::new(
-                    HumanPlayer { input_manager: Arc::clone(&input_manager) }, 
-                    HumanPlayer { input_manager: Arc::clone(&input_manager) }, 
-                    rule.clone()
-                ),
-                bot: GameData::new(
-                    HumanPlayer { input_manager: Arc::clone(&input_manager) }, 
-                    BotPlayer::new(
-                        model::NegamaxModel::new(
-                            5,
-                            eval::PatternEval { rule: rule.clone() },
-                            prune::NeighborPrune,
-                            rule.clone()
-                        )
-                    ),
-                    rule.clone(),
-                ),
-                twobot: GameData::new(
-                    BotPlayer::new(
-                        model::NegamaxModel::new(
-                            5,
-                            eval::PatternEval { rule: rule.clone() },
-                            prune::NeighborPrune,
-                            rule.clone()
-                        )
-                    ),
-                    BotPlayer::new(
-                        model::NegamaxModel::new(
-                            5,
-                            eval::PatternEval { rule: rule.clone() },
-                            prune::NeighborPrune,
-                            rule.clone()
-                        )
-                    ),
-                    rule.clone(),
-                )
-            },
+            rule,
+            opening,
+            size,
+            bots,
+            games: rule.games(opening, size, bots, &input_manager),
             ui_setting: UiSetting {
                 board_size: 360.0,
                 grid_stroke: egui::Stroke::new(
                     1.0, egui::Color32::GRAY
                 ),
-                stone_size: 10.0,
+                stone_ratio: 0.42,
                 stone_color_black: egui::Color32::BLACK,
                 stone_color_white: egui::Color32::WHITE,
                 stone_outline: egui::Stroke::new(
                     2.0, egui::Color32::DARK_GRAY
                 ),
+                forbidden_stroke: egui::Stroke::new(
+                    3.0, egui::Color32::RED
+                ),
+                analysis_color: egui::Color32::from_rgb(30, 120, 255),
             },
             input_manager: Arc::clone(&input_manager),
         };
 
-        result.games.pvp.trigger_start();
-        result.games.bot.trigger_start();
-        result.games.twobot.trigger_start();
+        result.games.trigger_start();
 
         result
     }
@@ -98,68 +182,175 @@ impl Default for MyApp {
 enum PlayerAction {
     Move(Move),
     Thinking,
+    // no move to play, the game is lost
+    Resign,
 }
 
 trait GamePlayer {
-    fn turn_start(&mut self, board: &Board, last_mv: Move) { }
+    /// `clock`: time left for the player, the move is lost on time after that
+    fn turn_start(&mut self, _board: &Board, _last_mv: Move, _clock: Clock) { }
 
     fn poll_move(&mut self) -> PlayerAction;
 
-    fn rejected(&mut self, board: &Board, reason: PutError);
+    fn rejected(&mut self, board: &Board, mv: Move, reason: PutError);
+
+    /// last rejected move and why it is forbidden, shown on the board
+    fn forbidden(&self) -> Option<&(Move, Forbidden)> { None }
+
+    // choice of swap openings, works like turn_start and poll_move
+
+    fn choice_start(&mut self, _board: &Board, _last_mv: Move, _options: &[Choice]) { }
+
+    fn poll_choice(&mut self) -> Option<Choice>;
+
+    /// forget the requested move or choice (e.g., on undo)
+    fn cancel(&mut self) { }
+
+    /// latest report of the thinking, if any new
+    fn poll_info(&mut self) -> Option<SearchInfo> {
+        None
+    }
+
+    /// bots answer on their own, undo/redo skip their turns
+    fn is_bot(&self) -> bool {
+        false
+    }
 }
 
 struct HumanPlayer {
     input_manager: Arc<RwLock<InputManager>>,
+    forbidden: Option<(Move, Forbidden)>,
+}
+
+impl HumanPlayer {
+    fn new(input_manager: &Arc<RwLock<InputManager>>) -> Self {
+        Self {
+            input_manager: Arc::clone(input_manager),
+            forbidden: None,
+        }
+    }
 }
 
 struct BotPlayer {
     model: Arc<Mutex<dyn Model + Send>>,
+    limits: SearchLimits,
+    // stops the running search
+    stop: StopToken,
+    info_rx: Option<mpsc::Receiver<SearchInfo>>,
     rx: Option<mpsc::Receiver<Option<Move>>>,
+    choice_rx: Option<mpsc::Receiver<Choice>>,
 }
 
 impl BotPlayer {
-    fn new<M>(model: M) -> Self 
+    fn new<M>(model: M, limits: SearchLimits) -> Self 
     where
         M: Model + Send + 'static
     {
         Self {
             model: Arc::new(Mutex::new(model)),
+            limits,
+            stop: StopToken::new(),
+            info_rx: None,
             rx: None,
+            choice_rx: None,
+        }
+    }
+
+    /// limits of a new search, the previous search is stopped
+    fn search_limits(&mut self) -> SearchLimits {
+        self.stop.stop();
+        self.stop = StopToken::new();
+
+        let (tx, rx) = mpsc::channel();
+        self.info_rx = Some(rx);
+
+        SearchLimits {
+            stop: self.stop.clone(),
+            info: Some(tx),
+            ..self.limits.clone()
         }
     }
 }
 
+impl Drop for BotPlayer {
+    // games are dropped when a new game starts
+    fn drop(&mut self) {
+        self.stop.stop();
+    }
+}
+
 impl GamePlayer for BotPlayer {
-    fn turn_start(&mut self, board: &Board, last_mv: Move) {
+    fn turn_start(&mut self, board: &Board, last_mv: Move, clock: Clock) {
         let (tx, rx) = mpsc::channel();
 
         self.rx = Some(rx);
 
-        let board = board.clone();
+        let board = *board;
         let model = Arc::clone(&self.model);
+        let limits = SearchLimits {
+            clock: Some(clock),
+            ..self.search_limits()
+        };
 
         
         let _ = thread::spawn(move || {
             let mut model = model.lock().unwrap();
-            let mv = model.next_move(&board, last_mv);
-
-            // todo sdasdfsdfsdfsfd
-            thread::sleep(Duration::from_millis(500));
-            tx.send(mv).unwrap();
+            let mv = model.next_move(&board, last_mv, limits);
+            // receiver is gone if the search is cancelled
+            let _ = tx.send(mv);
         });
 
     }
 
     fn poll_move(&mut self) -> PlayerAction {
-        if let Ok(recv) = self.rx.as_ref().unwrap().try_recv() {
-            PlayerAction::Move(recv.unwrap())
-        } else {
-            PlayerAction::Thinking
+        match self.rx.as_ref().map(|rx| rx.try_recv()) {
+            Some(Ok(Some(mv))) => PlayerAction::Move(mv),
+            // the model found no move to play
+            Some(Ok(None)) => PlayerAction::Resign,
+            _ => PlayerAction::Thinking,
         }
     }
 
-    fn rejected(&mut self, board: &Board, reason: PutError) {
-        // todo!();
+    fn rejected(&mut self, _board: &Board, mv: Move, reason: PutError) {
+        tracing::warn!("bot move {:?} rejected: {:?}", mv, reason);
+    }
+
+    fn choice_start(&mut self, board: &Board, last_mv: Move, options: &[Choice]) {
+        let (tx, rx) = mpsc::channel();
+
+        self.choice_rx = Some(rx);
+
+        let board = *board;
+        let options = options.to_vec();
+        let model = Arc::clone(&self.model);
+        let limits = self.search_limits();
+
+        let _ = thread::spawn(move || {
+            let mut model = model.lock().unwrap();
+            let choice = model.choose(&board, last_mv, &options, limits);
+            let _ = tx.send(choice);
+        });
+    }
+
+    fn poll_choice(&mut self) -> Option<Choice> {
+        self.choice_rx.as_ref()?.try_recv().ok()
+    }
+
+    fn cancel(&mut self) {
+        // the running search ends soon and releases the model,
+        // its result is dropped with the receiver
+        self.stop.stop();
+        self.rx = None;
+        self.choice_rx = None;
+        self.info_rx = None;
+    }
+
+    fn poll_info(&mut self) -> Option<SearchInfo> {
+        self.info_rx.as_ref()?.try_iter().last()
+    }
+
+    fn is_bot(&self) -> bool {
+        true
     }
 }
 
@@ -170,14 +361,31 @@ impl GamePlayer for HumanPlayer {
         
         let click = input_manager.get_click();
         if let Some(mv) = click {
+            self.forbidden = None;
             PlayerAction::Move(mv)
         } else {
             PlayerAction::Thinking
         }
     }
 
-    fn rejected(&mut self, board: &Board, reason: PutError) {
-        // todo!();
+    fn rejected(&mut self, _board: &Board, mv: Move, reason: PutError) {
+        if let PutError::Invalid(forbidden) = reason {
+            self.forbidden = Some((mv, forbidden));
+        }
+    }
+
+    fn forbidden(&self) -> Option<&(Move, Forbidden)> {
+        self.forbidden.as_ref()
+    }
+
+    fn poll_choice(&mut self) -> Option<Choice> {
+        let input_manager = self.input_manager.read().unwrap();
+
+        input_manager.get_choice()
+    }
+
+    fn cancel(&mut self) {
+        self.forbidden = None;
     }
 }
 
@@ -191,12 +399,102 @@ struct GameGroup {
     twobot: GameData,
 }
 
+impl GameGroup {
+    fn new<R>(
+        rule: R, opening: OpeningRule, size: usize, bots: [BotKind; 2],
+        input_manager: &Arc<RwLock<InputManager>>,
+    ) -> Self
+    where
+        R: Rule + Copy + 'static,
+    {
+        // pro openings restrict the placement, so bots should know it too
+        match opening {
+            OpeningRule::Pro => {
+                Self::with_rule(ProRule::pro(rule), opening, size, bots, input_manager)
+            },
+            OpeningRule::LongPro => {
+                Self::with_rule(ProRule::long_pro(rule), opening, size, bots, input_manager)
+            },
+            _ => Self::with_rule(rule, opening, size, bots, input_manager),
+        }
+    }
+
+    fn with_rule<R>(
+        rule: R, opening: OpeningRule, size: usize, bots: [BotKind; 2],
+        input_manager: &Arc<RwLock<InputManager>>,
+    ) -> Self
+    where
+        R: Rule + Copy + 'static,
+    {
+        Self {
+            pvp: GameData::new(
+                HumanPlayer::new(input_manager), 
+                HumanPlayer::new(input_manager), 
+                rule,
+                opening,
+                size,
+            ),
+            bot: GameData::new(
+                HumanPlayer::new(input_manager), 
+                bots[1].player(rule),
+                rule,
+                opening,
+                size,
+            ),
+            twobot: GameData::new(
+                bots[0].player(rule),
+                bots[1].player(rule),
+                rule,
+                opening,
+                size,
+            )
+        }
+    }
+
+    fn trigger_start(&mut self) {
+        self.pvp.trigger_start();
+        self.bot.trigger_start();
+        self.twobot.trigger_start();
+    }
+
+    fn cancel(&mut self) {
+        self.pvp.cancel();
+        self.bot.cancel();
+        self.twobot.cancel();
+    }
+}
+
 struct GameData {
-    board: Board,
-    black: Box<dyn GamePlayer>,
-    white: Box<dyn GamePlayer>,
+    record: Game,
+    // first player starts as black, but colors can be swapped by opening
+    first: Box<dyn GamePlayer>,
+    second: Box<dyn GamePlayer>,
     status: GameStatus,
-    rule: Box<dyn Rule>,
+    rule: Arc<dyn Rule>,
+    opening: Opening,
+    // opening state when each ply is reached, restored by undo/redo
+    openings: Vec<Opening>,
+    // latest thinking of a bot and its color
+    info: Option<(Player, SearchInfo)>,
+    // time left of the first and the second player
+    clocks: [Duration; 2],
+    // time left when each ply is reached, restored by undo/redo
+    clock_history: Vec<[Duration; 2]>,
+    // when the requested move was asked, None while choosing in openings
+    turn_start: Option<Instant>,
+    // vct line of the side to move (None if not found),
+    // with the hash of the analyzed position
+    analysis: Option<(u64, Option<Vec<Move>>)>,
+    // running analysis, with the hash of its position
+    analysis_rx: Option<(u64, mpsc::Receiver<Option<Vec<Move>>>)>,
+    analysis_stop: StopToken,
+}
+
+impl Drop for GameData {
+    // games are dropped when a new game starts
+    fn drop(&mut self) {
+        self.analysis_stop.stop();
+    }
 }
 
 enum GameStatus {
@@ -204,63 +502,242 @@ enum GameStatus {
 }
 
 impl GameData {
-    fn new<P1, P2, R>(black: P1, white: P2, rule: R) -> Self 
+    fn new<P1, P2, R>(
+        first: P1, second: P2, rule: R, opening: OpeningRule, size: usize,
+    ) -> Self 
     where
         P1: GamePlayer + 'static,
         P2: GamePlayer + 'static,
         R: Rule + 'static,
     {
+        let opening = Opening::new(opening);
         Self {
-            board: Board::blank(),
-            black: Box::new(black),
-            white: Box::new(white),
+            record: Game::new(size),
+            first: Box::new(first),
+            second: Box::new(second),
             status: GameStatus::Ongo,
-            rule: Box::new(rule),
+            rule: Arc::new(rule),
+            opening,
+            openings: vec![opening],
+            info: None,
+            clocks: [GAME_CLOCK.remaining; 2],
+            clock_history: vec![[GAME_CLOCK.remaining; 2]],
+            turn_start: None,
+            analysis: None,
+            analysis_rx: None,
+            analysis_stop: StopToken::new(),
         }
     }
 
+    fn board(&self) -> &Board {
+        self.record.board()
+    }
+
+    fn last_mv(&self) -> Move {
+        // center is dummy data before the first move
+        self.record.last_move().unwrap_or(self.board().center())
+    }
+
+    fn requested_side(&self) -> Side {
+        match self.opening.request(self.record.board()) {
+            Request::Put(side) | Request::Choose(side, _) => side,
+        }
+    }
+
+    /// player who should answer the current request
+    fn requested(&mut self) -> &mut Box<dyn GamePlayer> {
+        match self.requested_side() {
+            Side::First => &mut self.first,
+            Side::Second => &mut self.second,
+        }
+    }
+
+    /// takes back moves until a human is to play
+    /// (in bot mode, the bot move and the human move)
+    fn undo(&mut self) {
+        self.travel(Game::undo);
+    }
+
+    fn redo(&mut self) {
+        self.travel(Game::redo);
+    }
+
+    /// time left of the player, counting down while the player is to move
+    fn time_left(&self, side: Side) -> Duration {
+        let remaining = self.clocks[side as usize];
+        match self.turn_start {
+            Some(start) if self.requested_side() == side => remaining.saturating_sub(start.elapsed()),
+            _ => remaining,
+        }
+    }
+
+    /// charges the time of the move to the player, then adds the increment
+    /// (called after the move is put, when the other side is requested)
+    fn spend(&mut self, side: Side) {
+        if let Some(start) = self.turn_start.take() {
+            let clock = &mut self.clocks[side as usize];
+            *clock = clock.saturating_sub(start.elapsed()) + GAME_CLOCK.increment;
+        }
+    }
+
+    /// searches a victory by continuous threats of the side to move
+    /// (on another thread, the result is taken by `poll_analysis`)
+    fn analyze(&mut self) {
+        self.analysis_stop.stop();
+        self.analysis_stop = StopToken::new();
+
+        let (tx, rx) = mpsc::channel();
+        let board = *self.board();
+        self.analysis_rx = Some((board.hash(), rx));
+
+        let rule = Arc::clone(&self.rule);
+        let stop = self.analysis_stop.clone();
+        let _ = thread::spawn(move || {
+            let mut vct = Vct::new(rule.as_ref());
+            vct.budget = vct.budget.until(None, stop);
+            // receiver is gone if the analysis is cancelled
+            let _ = tx.send(vct.solve(&board));
+        });
+    }
+
+    fn poll_analysis(&mut self) {
+        if let Some((hash, rx)) = &self.analysis_rx
+            && let Ok(line) = rx.try_recv()
+        {
+            self.analysis = Some((*hash, line));
+            self.analysis_rx = None;
+        }
+    }
+
+    /// whether the current position is being analyzed
+    fn analyzing(&self) -> bool {
+        self.analysis_rx.as_ref().is_some_and(|(hash, _)| *hash == self.board().hash())
+    }
+
+    /// result of the analysis, only while the position is not changed
+    fn analysis(&self) -> Option<&Option<Vec<Move>>> {
+        self.analysis.as_ref()
+            .filter(|(hash, _)| *hash == self.board().hash())
+            .map(|(_, line)| line)
+    }
+
+    fn can_undo(&self) -> bool {
+        self.record.can_undo()
+    }
+
+    fn can_redo(&self) -> bool {
+        self.record.can_redo()
+    }
+
+    /// stops waiting for the players and the analysis
+    fn cancel(&mut self) {
+        self.first.cancel();
+        self.second.cancel();
+        self.turn_start = None;
+        self.analysis_stop.stop();
+        self.analysis_rx = None;
+    }
+
+    fn travel(&mut self, step: fn(&mut Game) -> Option<Move>) {
+        self.cancel();
+        self.info = None;
+
+        while step(&mut self.record).is_some() {
+            let ply = self.board().ply() as usize;
+            self.opening = self.openings[ply];
+            self.clocks = self.clock_history[ply];
+            self.status = self.outcome_status();
+            if matches!(self.status, GameStatus::Ongo) && self.requested().is_bot() {
+                continue;
+            }
+            break;
+        }
+
+        if matches!(self.status, GameStatus::Ongo) {
+            self.start_request();
+        }
+    }
+
+    /// status by the outcome of the last move
+    fn outcome_status(&self) -> GameStatus {
+        // the last move is made by the opposite of the side to move
+        let last = self.board().turn().next();
+        match self.record.outcome() {
+            PutOutcome::Continue => GameStatus::Ongo,
+            PutOutcome::Win => GameStatus::Win(last),
+            PutOutcome::Draw => GameStatus::Draw,
+        }
+    }
+
+    /// last move rejected from either player
+    fn forbidden(&self) -> Option<&(Move, Forbidden)> {
+        self.first.forbidden().or_else(|| self.second.forbidden())
+    }
+
     fn trigger_start(&mut self) {
-        // black starts first
-        // (7, 7) is dummy data, TODO: fix it to Option<Move>
-        self.black.turn_start(&self.board, Move { x: 7, y: 7 });
+        // first player (black) starts first
+        self.start_request();
+    }
+
+    /// let the player know what the game is waiting for
+    fn start_request(&mut self) {
+        let board = *self.board();
+        let last_mv = self.last_mv();
+        match self.opening.request(&board) {
+            Request::Put(side) => {
+                let clock = Clock {
+                    remaining: self.clocks[side as usize],
+                    ..GAME_CLOCK
+                };
+                self.turn_start = Some(Instant::now());
+                self.requested().turn_start(&board, last_mv, clock);
+            },
+            Request::Choose(_, options) => {
+                self.turn_start = None;
+                self.requested().choice_start(&board, last_mv, &options);
+            },
+        }
     }
 }
 
 struct UiSetting {
     board_size: f32,
     grid_stroke: egui::Stroke,
-    stone_size: f32,
+    /// radius of the stone relative to the cell
+    stone_ratio: f32,
     stone_color_black: egui::Color32,
     stone_color_white: egui::Color32,
     stone_outline: egui::Stroke,
+    forbidden_stroke: egui::Stroke,
+    /// numbers of the analyzed line
+    analysis_color: egui::Color32,
 }
 
 
 // handle input events
 // currently this handles mouse event
 
+#[derive(Default)]
 struct InputManager {
-    mouse: Option<Move>
-}
-
-impl Default for InputManager {
-    fn default() -> Self {
-        Self {
-            mouse: None,
-        }
-    }
+    mouse: Option<Move>,
+    choice: Option<Choice>,
 }
 
 impl InputManager {
-    fn update(&mut self, ui_setting: &UiSetting, resp: &egui::Response) {
+    fn update(
+        &mut self, ui_setting: &UiSetting, size: usize,
+        resp: &egui::Response, choice: Option<Choice>,
+    ) {
+        self.choice = choice;
+
         if resp.clicked() {
-            let cell = ui_setting.board_size / 15.0;
+            let cell = ui_setting.board_size / size as f32;
 
             let pos = resp.interact_pointer_pos().unwrap();
             let local = (pos - resp.rect.min)
                 .clamp(eframe::emath::Vec2::ZERO, resp.rect.size());
             let coord = (local / cell).floor();
-            let mv = Move::new(coord.x as usize, coord.y as usize);
+            let mv = Move::new(coord.x as usize, coord.y as usize, size);
 
             self.mouse = mv;
             
@@ -273,10 +750,19 @@ impl InputManager {
         
         self.mouse
     }
+
+    fn get_choice(&self) -> Option<Choice> {
+        self.choice
+    }
 }
 
 // main ui logic
 impl eframe::App for MyApp {
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        // don't leave bot searches running
+        self.games.cancel();
+    }
+
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         // --- top panel ---
         egui::TopBottomPanel::top("mode_switcher").show(ctx, |ui| {
@@ -288,6 +774,56 @@ impl eframe::App for MyApp {
                 ui.radio_value(&mut self.mode, AppMode::Bot, "Bot");
                 ui.radio_value(&mut self.mode, AppMode::TwoBot, "Bot vs Bot");
                 ui.radio_value(&mut self.mode, AppMode::About, "About");
+
+                ui.separator();
+
+                // changing the rule starts new games
+                let prev_rule = self.rule;
+                egui::ComboBox::from_label("Rule")
+                    .selected_text(self.rule.name())
+                    .show_ui(ui, |ui| {
+                        for kind in RuleKind::ALL {
+                            ui.selectable_value(&mut self.rule, kind, kind.name());
+                        }
+                    });
+
+                let prev_opening = self.opening;
+                egui::ComboBox::from_label("Opening")
+                    .selected_text(self.opening.name())
+                    .show_ui(ui, |ui| {
+                        for opening in OpeningRule::ALL {
+                            ui.selectable_value(&mut self.opening, opening, opening.name());
+                        }
+                    });
+
+                let prev_size = self.size;
+                egui::ComboBox::from_label("Board")
+                    .selected_text(format!("{0}x{0}", self.size))
+                    .show_ui(ui, |ui| {
+                        for size in BOARD_SIZES {
+                            ui.selectable_value(&mut self.size, size, format!("{0}x{0}", size));
+                        }
+                    });
+
+                let prev_bots = self.bots;
+                for (bot, label) in self.bots.iter_mut().zip(["First bot", "Second bot"]) {
+                    egui::ComboBox::from_label(label)
+                        .selected_text(bot.name())
+                        .show_ui(ui, |ui| {
+                            for kind in BotKind::ALL {
+                                ui.selectable_value(bot, kind, kind.name());
+                            }
+                        });
+                }
+
+                if self.rule != prev_rule
+                    || self.opening != prev_opening
+                    || self.size != prev_size
+                    || self.bots != prev_bots
+                {
+                    self.games = self.rule.games(self.opening, self.size, self.bots, &self.input_manager);
+                    self.games.trigger_start();
+                }
             })
         });
 
@@ -298,38 +834,56 @@ impl eframe::App for MyApp {
                 ui.label("made by mark-2008");
 
             } else {
-                let mut game = match self.mode {
+                let game = match self.mode {
                     AppMode::Pvp => &mut self.games.pvp,
                     AppMode::Bot => &mut self.games.bot,
                     AppMode::TwoBot => &mut self.games.twobot,
                     _ => unreachable!(),
                 };
 
-                let (resp, painter) = game_template(
-                    &self.ui_setting, &game, ui,
+                if matches!(self.mode, AppMode::Pvp | AppMode::Bot) {
+                    ui.horizontal(|ui| {
+                        if ui.add_enabled(game.can_undo(), egui::Button::new("Undo")).clicked() {
+                            game.undo();
+                        }
+                        if ui.add_enabled(game.can_redo(), egui::Button::new("Redo")).clicked() {
+                            game.redo();
+                        }
+                        if ui.button("Analyze").clicked() {
+                            game.analyze();
+                        }
+                    });
+                }
+
+                let (resp, painter, choice) = game_template(
+                    &self.ui_setting, game, ui,
                 );
 
                 {
                     let mut input = self.input_manager.write().unwrap();
-                    input.update(&self.ui_setting, &resp);
+                    input.update(&self.ui_setting, game.board().size(), &resp, choice);
                 }
                 
                 game_logic(game);
 
                 // draw current mode
                 let rect = resp.rect;
-                draw_board(&self.ui_setting, &game, painter, &rect);
+                draw_board(&self.ui_setting, game, painter, &rect);
             }
         });
+
+        // clocks run and bots answer without any input
+        ctx.request_repaint_after(Duration::from_millis(200));
     }
 }
 
 /// template for game 
+/// returns the choice button clicked for swap openings
 fn game_template(
     setting: &UiSetting,
     game: &GameData,
     ui: &mut egui::Ui,
-) -> (egui::Response, egui::Painter) {
+) -> (egui::Response, egui::Painter, Option<Choice>) {
     ui.horizontal(|ui| {
         // game status message
         if matches!(game.status, GameStatus::Draw) {
@@ -344,62 +898,205 @@ fn game_template(
         }
         
         // turn, ply text
-        let turn_text = match game.board.turn() {
+        let turn_text = match game.board().turn() {
             Turn::Black => "Black",
             Turn::White => "White",
         };
 
-        let mut label_text = format!(
+        let label_text = format!(
             "turn: {}, ply: {}",
             turn_text,
-            game.board.ply(),
+            game.board().ply(),
         );
 
         ui.label(label_text);
+
+        // game clocks
+        let clock_text = |color| {
+            let secs = game.time_left(game.opening.side(color)).as_secs();
+            format!("{}:{:02}", secs / 60, secs % 60)
+        };
+        ui.label(format!(
+            "black: {}, white: {}",
+            clock_text(Player::Black),
+            clock_text(Player::White),
+        ));
+
+        // reason of the rejected move
+        if let Some((_, forbidden)) = game.forbidden() {
+            let forbidden_text = match forbidden.kind {
+                ForbiddenKind::DoubleThree => "Forbidden: double three (3-3)",
+                ForbiddenKind::DoubleFour => "Forbidden: double four (4-4)",
+                ForbiddenKind::Overline => "Forbidden: overline",
+                ForbiddenKind::Other => "Forbidden move",
+            };
+            ui.colored_label(setting.forbidden_stroke.color, forbidden_text);
+        }
     });
+
+    // thinking of the bot
+    if let Some((color, info)) = &game.info {
+        let color_text = match color {
+            Turn::Black => "Black",
+            Turn::White => "White",
+        };
+        let pv_text: Vec<String> = info.pv.iter()
+            .map(|mv| format!("({}, {})", mv.x, mv.y))
+            .collect();
+        ui.label(format!(
+            "{} bot - depth: {}, score: {:.1}, nodes: {}, nps: {}, tt hits: {}, cutoffs: {}, time: {:.2}s",
+            color_text,
+            info.depth,
+            info.score,
+            info.stats.nodes,
+            info.nps,
+            info.stats.tt_hits,
+            info.stats.cutoffs,
+            info.elapsed.as_secs_f32(),
+        ));
+        ui.label(format!("pv: {}", pv_text.join(" ")));
+    }
+
+    // line of the analysis, numbered on the board
+    if game.analyzing() {
+        ui.colored_label(setting.analysis_color, "vct: analyzing...");
+    }
+    match game.analysis() {
+        Some(Some(line)) => {
+            let line_text: Vec<String> = line.iter()
+                .map(|mv| format!("({}, {})", mv.x, mv.y))
+                .collect();
+            ui.colored_label(setting.analysis_color, format!("vct: {}", line_text.join(" ")));
+        },
+        Some(None) => {
+            ui.colored_label(setting.analysis_color, "vct: not found");
+        },
+        None => (),
+    }
+
+    // colors of the players and choice buttons for swap openings
+    let mut choice = None;
+    if matches!(game.opening.rule(), OpeningRule::Swap | OpeningRule::Swap2) {
+        ui.horizontal(|ui| {
+            let color_text = |side| match game.opening.color(side) {
+                Turn::Black => "Black",
+                Turn::White => "White",
+            };
+            ui.label(format!(
+                "first: {}, second: {}",
+                color_text(Side::First),
+                color_text(Side::Second),
+            ));
+
+            if let Request::Choose(side, options) = game.opening.request(game.board()) {
+                let side_text = match side {
+                    Side::First => "first",
+                    Side::Second => "second",
+                };
+                ui.label(format!("{} player chooses:", side_text));
+                for option in options {
+                    let option_text = match option {
+                        Choice::Black => "Black",
+                        Choice::White => "White",
+                        Choice::PutTwo => "Put two more",
+                    };
+                    if ui.button(option_text).clicked() {
+                        choice = Some(option);
+                    }
+                }
+            }
+        });
+    }
+
     ui.separator();
     let (resp, painter) = ui.allocate_painter(
         egui::Vec2::splat(setting.board_size), 
         egui::Sense::click()
     );
 
-    (resp, painter)
+    (resp, painter, choice)
 }
 
 fn game_logic(game: &mut GameData) {
+    game.poll_analysis();
+
+    for side in [Side::First, Side::Second] {
+        let player = match side {
+            Side::First => &mut game.first,
+            Side::Second => &mut game.second,
+        };
+        if let Some(info) = player.poll_info() {
+            game.info = Some((game.opening.color(side), info));
+        }
+    }
+
     if matches!(game.status, GameStatus::Win(_) | GameStatus::Draw) {
         return;
     }
 
-    let turn = game.board.turn();
-    let game_player = match turn {
-        Turn::Black => &mut game.black,
-        Turn::White => &mut game.white,
+    let side = match game.opening.request(game.record.board()) {
+        Request::Put(side) => side,
+        Request::Choose(side, _) => {
+            let game_player = match side {
+                Side::First => &mut game.first,
+                Side::Second => &mut game.second,
+            };
+            if let Some(choice) = game_player.poll_choice()
+                && game.opening.choose(game.record.board(), side, choice)
+            {
+                // later moves were played after another choice
+                game.record.clear_redo();
+                let ply = game.record.board().ply() as usize;
+                game.openings.truncate(ply + 1);
+                game.clock_history.truncate(ply + 1);
+                game.start_request();
+            }
+            return;
+        },
+    };
+
+    let turn = game.record.board().turn();
+    if game.time_left(side).is_zero() {
+        // lost on time
+        game.cancel();
+        game.status = GameStatus::Win(turn.next());
+        return;
+    }
+
+    let game_player = match side {
+        Side::First => &mut game.first,
+        Side::Second => &mut game.second,
     };
 
     let action = game_player.poll_move();
     
     match action {
         PlayerAction::Move(mv) => {
-            let result = game.rule.put(&mut game.board, mv, turn);
+            let result = game.record.put(game.rule.as_ref(), mv);
             match result {
                 Ok(outcome) => {
+                    game.spend(side);
+                    let ply = game.record.board().ply() as usize;
+                    game.openings.truncate(ply);
+                    game.openings.push(game.opening);
+                    game.clock_history.truncate(ply);
+                    game.clock_history.push(game.clocks);
                     game.status = match outcome {
                         PutOutcome::Continue => {
-                            let next_player = match turn.next() {
-                                Turn::Black => &mut game.black,
-                                Turn::White => &mut game.white,
-                            };
-                            next_player.turn_start(&game.board, mv);
+                            game.start_request();
                             GameStatus::Ongo
                         },
                         PutOutcome::Win => GameStatus::Win(turn),
                         PutOutcome::Draw => GameStatus::Draw,
                     };
                 },
-                Err(error) => game_player.rejected(&game.board, error),
+                Err(error) => game_player.rejected(game.record.board(), mv, error),
             }
         },
+        PlayerAction::Resign => {
+            game.cancel();
+            game.status = GameStatus::Win(turn.next());
+        },
         PlayerAction::Thinking => (),
     }
 }
@@ -410,10 +1107,12 @@ fn draw_board(
     painter: egui::Painter,
     rect: &egui::Rect,
  ) {
-    let cell = setting.board_size / 15.0;
+    let size = game.board().size();
+    let cell = setting.board_size / size as f32;
+    let stone_size = cell * setting.stone_ratio;
 
     // draw vertical lines
-    for i in 0..15 {
+    for i in 0..size {
         let x = rect.left() + cell * (i as f32 + 0.5);
         painter.line_segment(
             [
@@ -425,7 +1124,7 @@ fn draw_board(
     }
 
     // draw horizontal lines
-    for i in 0..15 {
+    for i in 0..size {
         let y = rect.top() + cell * (i as f32 + 0.5);
         painter.line_segment(
             [
@@ -437,9 +1136,9 @@ fn draw_board(
     }
 
     // draw stones
-    for i in 0..15 {
-        for j in 0..15 {
-            let stone = game.board.get(Move { x: i, y: j });
+    for i in 0..size {
+        for j in 0..size {
+            let stone = game.board().get(Move { x: i, y: j });
             if stone != Stone::None {
                 let center = egui::Pos2::new(
                     rect.left() + cell * (i as f32 + 0.5),
@@ -453,11 +1152,65 @@ fn draw_board(
 
                 painter.circle(
                     center, 
-                    setting.stone_size, 
+                    stone_size, 
                     fill_color, 
                     setting.stone_outline,
                 );
             }
         }
     }
+
+    // highlight lines forming the forbidden pattern
+    if let Some((mv, forbidden)) = game.forbidden() {
+        let center = |mv: &Move| egui::Pos2::new(
+            rect.left() + cell * (mv.x as f32 + 0.5),
+            rect.top() + cell * (mv.y as f32 + 0.5),
+        );
+
+        for line in &forbidden.lines {
+            // stones are ordered along the line
+            if let (Some(first), Some(last)) = (line.first(), line.last()) {
+                painter.line_segment(
+                    [center(first), center(last)],
+                    setting.forbidden_stroke,
+                );
+            }
+            for stone in line {
+                painter.circle_stroke(
+                    center(stone),
+                    stone_size,
+                    setting.forbidden_stroke,
+                );
+            }
+        }
+
+        // cross on the rejected move
+        let c = center(mv);
+        let d = stone_size * 0.7;
+        painter.line_segment(
+            [c + egui::vec2(-d, -d), c + egui::vec2(d, d)],
+            setting.forbidden_stroke,
+        );
+        painter.line_segment(
+            [c + egui::vec2(-d, d), c + egui::vec2(d, -d)],
+            setting.forbidden_stroke,
+        );
+    }
+
+    // order of the moves in the analyzed line
+    if let Some(Some(line)) = game.analysis() {
+        for (i, mv) in line.iter().enumerate() {
+            let center = egui::Pos2::new(
+                rect.left() + cell * (mv.x as f32 + 0.5),
+                rect.top() + cell * (mv.y as f32 + 0.5),
+            );
+            painter.text(
+                center,
+                egui::Align2::CENTER_CENTER,
+                (i + 1).to_string(),
+                egui::FontId::proportional(stone_size),
+                setting.analysis_color,
+            );
+        }
+    }
 }
\ No newline at end of file